@@ -0,0 +1,155 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use base64::Engine;
+use reqwest::header::HeaderValue;
+
+use crate::oauth2::OAuth2;
+
+// A token file is checked for changes at most this often
+const TOKEN_FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Headers whose values are hidden from the dashboard and the reports
+const SENSITIVE_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+];
+
+// Auth is the credentials sent with every request
+#[derive(Debug, Clone)]
+pub enum Auth {
+    Basic { username: String, password: String },
+    Bearer(String),
+    // Bearer token read from a file, reloaded when the file changes
+    BearerFile(Arc<TokenFile>),
+    // Token of the OAuth2 client credentials grant, refreshed before it expires
+    OAuth2(Arc<OAuth2>),
+}
+
+#[derive(Debug)]
+pub struct TokenFile {
+    path: String,
+    state: Mutex<TokenState>,
+}
+
+#[derive(Debug)]
+struct TokenState {
+    modified: Option<SystemTime>,
+    checked: Instant,
+    header: HeaderValue,
+}
+
+impl Auth {
+    /// Parse the credentials of --basic-auth, e.g. user:pass
+    pub fn basic(credentials: &str) -> Result<Self, String> {
+        let (username, password) = credentials
+            .split_once(':')
+            .ok_or_else(|| "Basic auth credentials must be user:pass".to_string())?;
+
+        Ok(Auth::Basic {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    pub fn bearer_file(path: &str) -> Result<Self, String> {
+        Ok(Auth::BearerFile(Arc::new(TokenFile::open(path)?)))
+    }
+
+    /// Authorization header of static credentials, set on the client
+    /// None for credentials that change during the test
+    pub fn static_header(&self) -> Option<Result<HeaderValue, String>> {
+        let value = match self {
+            Auth::Basic { username, password } => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password))
+            ),
+            Auth::Bearer(token) => format!("Bearer {}", token),
+            Auth::BearerFile(_) | Auth::OAuth2(_) => return None,
+        };
+        Some(sensitive_header(&value))
+    }
+
+    /// Authorization header sent with a request, for credentials that change during the test
+    pub fn request_header(&self) -> Option<HeaderValue> {
+        match self {
+            Auth::BearerFile(file) => Some(file.header()),
+            Auth::OAuth2(oauth2) => oauth2.header(),
+            _ => None,
+        }
+    }
+
+    /// Description of the credentials with the secrets redacted
+    pub fn describe(&self) -> String {
+        match self {
+            Auth::Basic { username, .. } => format!("Basic {}:***", username),
+            Auth::Bearer(_) => "Bearer ***".to_string(),
+            Auth::BearerFile(file) => format!("Bearer from {}", file.path),
+            Auth::OAuth2(oauth2) => format!("OAuth2 client {}", oauth2.client_id()),
+        }
+    }
+}
+
+impl TokenFile {
+    fn open(path: &str) -> Result<Self, String> {
+        let (modified, header) = Self::read(path)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            state: Mutex::new(TokenState {
+                modified,
+                checked: Instant::now(),
+                header,
+            }),
+        })
+    }
+
+    fn read(path: &str) -> Result<(Option<SystemTime>, HeaderValue), String> {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let token = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read bearer token from {}: {}", path, e))?;
+        let header = sensitive_header(&format!("Bearer {}", token.trim()))?;
+        Ok((modified, header))
+    }
+
+    /// Current Authorization header, the file is read again when it was modified
+    /// The previous token is kept when the file can't be read, e.g. while it is rewritten
+    fn header(&self) -> HeaderValue {
+        let mut state = self.state.lock().unwrap();
+
+        if state.checked.elapsed() >= TOKEN_FILE_CHECK_INTERVAL {
+            state.checked = Instant::now();
+            let modified = std::fs::metadata(&self.path)
+                .and_then(|m| m.modified())
+                .ok();
+            if modified != state.modified {
+                if let Ok((modified, header)) = Self::read(&self.path) {
+                    state.modified = modified;
+                    state.header = header;
+                }
+            }
+        }
+
+        state.header.clone()
+    }
+}
+
+fn sensitive_header(value: &str) -> Result<HeaderValue, String> {
+    let mut header = HeaderValue::from_str(value)
+        .map_err(|_| "Credentials contain invalid header characters".to_string())?;
+    header.set_sensitive(true);
+    Ok(header)
+}
+
+/// Hide the value of a "Name: value" header when it holds credentials
+pub fn redact_header(header: &str) -> String {
+    match header.split_once(':') {
+        Some((name, _)) if SENSITIVE_HEADERS.contains(&name.trim().to_lowercase().as_str()) => {
+            format!("{}: ***", name.trim())
+        }
+        _ => header.to_string(),
+    }
+}
@@ -0,0 +1,322 @@
+use reqwest::header::HeaderName;
+use reqwest::StatusCode;
+use serde::Serialize;
+use serde_json_path::JsonPath;
+
+use crate::response::Response;
+use crate::utils::parse_duration;
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Status(StatusSet),
+    BodyContains(String),
+    JsonEquals(JsonPath, serde_json::Value),
+    HeaderPresent(HeaderName),
+    // Body size in bytes, bounds included
+    Size(u64, u64),
+    LatencyBelow(std::time::Duration),
+}
+
+// StatusSet is a list of status codes and ranges, e.g. 200,201,304 or 200-299
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusSet(Vec<(u16, u16)>);
+
+impl Default for StatusSet {
+    fn default() -> Self {
+        Self(vec![(200, 299)])
+    }
+}
+
+impl StatusSet {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid status codes: {}, expected e.g. 200,201,304 or 200-299",
+                input
+            )
+        };
+        let code = |code: &str| match code.trim().parse::<u16>() {
+            Ok(code) if (100..=999).contains(&code) => Ok(code),
+            _ => Err(invalid()),
+        };
+
+        input
+            .split(',')
+            .map(|part| match part.split_once('-') {
+                Some((min, max)) => {
+                    let (min, max) = (code(min)?, code(max)?);
+                    if min > max {
+                        return Err(invalid());
+                    }
+                    Ok((min, max))
+                }
+                None => code(part).map(|code| (code, code)),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    pub fn contains(&self, status: StatusCode) -> bool {
+        let status = status.as_u16();
+        self.0
+            .iter()
+            .any(|(min, max)| (*min..=*max).contains(&status))
+    }
+}
+
+// Check is a condition every response must meet to succeed,
+// e.g. status in 200,201 or body contains "ok"
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub expression: String,
+    condition: Condition,
+}
+
+// Criteria is what a response must meet to succeed, an expected status and the checks
+#[derive(Debug, Clone, Default)]
+pub struct Criteria {
+    pub expect_status: StatusSet,
+    pub checks: Vec<Check>,
+}
+
+// Passes and failures of a check over a test
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckCount {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl Check {
+    /// Parse a check expression:
+    /// status in 200,201,300-399 | body contains text | json $.path == value | header Name
+    /// | size < 1000 | size in 100..5000 | latency < 500ms
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let invalid = |usage: &str| format!("Invalid check: {}, expected {}", input, usage);
+        let (subject, rest) = input
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("Invalid check: {}", input))?;
+        let rest = rest.trim();
+
+        let condition = match subject.to_lowercase().as_str() {
+            "status" => {
+                let codes = rest
+                    .strip_prefix("in ")
+                    .or_else(|| rest.strip_prefix("=="))
+                    .ok_or_else(|| invalid("status in 200,201"))?;
+                Condition::Status(
+                    StatusSet::parse(codes).map_err(|_| invalid("status in 200,201"))?,
+                )
+            }
+            "body" => {
+                let text = rest
+                    .strip_prefix("contains ")
+                    .ok_or_else(|| invalid("body contains text"))?;
+                Condition::BodyContains(unquote(text).to_string())
+            }
+            "json" => {
+                let (path, value) = rest
+                    .split_once("==")
+                    .ok_or_else(|| invalid("json $.path == value"))?;
+                let path = JsonPath::parse(path.trim())
+                    .map_err(|e| format!("Invalid JSONPath in check {}: {}", input, e))?;
+                // Values that are not JSON are compared as strings, e.g. ok for "ok"
+                let value = value.trim();
+                let value = serde_json::from_str(value)
+                    .unwrap_or_else(|_| serde_json::Value::String(unquote(value).to_string()));
+                Condition::JsonEquals(path, value)
+            }
+            "header" => Condition::HeaderPresent(
+                HeaderName::from_bytes(rest.as_bytes()).map_err(|_| invalid("header Name"))?,
+            ),
+            "size" => {
+                let usage = "size < 1000 or size in 100..5000";
+                let number = |value: &str| value.trim().parse::<u64>().map_err(|_| invalid(usage));
+                let (min, max) = if let Some(range) = rest.strip_prefix("in ") {
+                    let (min, max) = range.split_once("..").ok_or_else(|| invalid(usage))?;
+                    (number(min)?, number(max)?)
+                } else if let Some(max) = rest.strip_prefix("<=") {
+                    (0, number(max)?)
+                } else if let Some(max) = rest.strip_prefix('<') {
+                    (0, number(max)?.saturating_sub(1))
+                } else if let Some(min) = rest.strip_prefix(">=") {
+                    (number(min)?, u64::MAX)
+                } else if let Some(min) = rest.strip_prefix('>') {
+                    (number(min)?.saturating_add(1), u64::MAX)
+                } else {
+                    return Err(invalid(usage));
+                };
+                Condition::Size(min, max)
+            }
+            "latency" => {
+                let bound = rest
+                    .strip_prefix('<')
+                    .ok_or_else(|| invalid("latency < 500ms"))?;
+                Condition::LatencyBelow(
+                    parse_duration(bound).map_err(|e| format!("{} in check: {}", e, input))?,
+                )
+            }
+            _ => return Err(format!("Unknown check: {}", input)),
+        };
+
+        Ok(Self {
+            expression: input.trim().to_string(),
+            condition,
+        })
+    }
+
+    fn passes(&self, response: &Response) -> bool {
+        match &self.condition {
+            Condition::Status(codes) => codes.contains(response.status),
+            Condition::BodyContains(text) => response.body.contains(text.as_str()),
+            Condition::JsonEquals(path, value) => {
+                serde_json::from_str::<serde_json::Value>(response.body)
+                    .ok()
+                    .and_then(|json| path.query(&json).first().map(|found| found == value))
+                    .unwrap_or(false)
+            }
+            Condition::HeaderPresent(name) => response.headers.contains_key(name),
+            Condition::Size(min, max) => (*min..=*max).contains(&(response.body.len() as u64)),
+            Condition::LatencyBelow(bound) => response.duration < *bound,
+        }
+    }
+}
+
+fn unquote(text: &str) -> &str {
+    text.strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text)
+}
+
+impl CheckCount {
+    /// Responses that passed, in percent of the responses checked
+    pub fn pass_rate(&self) -> f64 {
+        let total = self.passed + self.failed;
+        if total == 0 {
+            return 0.0;
+        }
+        self.passed as f64 * 100.0 / total as f64
+    }
+}
+
+/// Whether each check passes for a response
+pub fn evaluate(checks: &[Check], response: &Response) -> Vec<bool> {
+    checks.iter().map(|check| check.passes(response)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    fn passes(check: &str, body: &str) -> bool {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("1"));
+        Check::parse(check).unwrap().passes(&Response {
+            status: StatusCode::CREATED,
+            headers: &headers,
+            body,
+            duration: std::time::Duration::from_millis(120),
+        })
+    }
+
+    #[test]
+    fn status_and_header_checks() {
+        assert!(passes("status in 200,201", ""));
+        assert!(passes("status == 201", ""));
+        assert!(!passes("status in 200,300-399", ""));
+        assert!(passes("header X-Request-Id", ""));
+        assert!(!passes("header X-Other", ""));
+    }
+
+    #[test]
+    fn body_checks() {
+        let body = r#"{"status": "ok", "count": 3, "items": [{"id": 1}]}"#;
+        assert!(passes(r#"body contains "ok""#, body));
+        assert!(!passes("body contains error", body));
+        assert!(passes(r#"json $.status == "ok""#, body));
+        assert!(passes("json $.status == ok", body));
+        assert!(passes("json $.count == 3", body));
+        assert!(passes("json $.items[0].id == 1", body));
+        assert!(!passes("json $.count == 4", body));
+        assert!(!passes("json $.missing == 1", body));
+        assert!(!passes("json $.status == ok", "not json"));
+    }
+
+    #[test]
+    fn size_and_latency_checks() {
+        assert!(passes("size < 4", "abc"));
+        assert!(!passes("size < 3", "abc"));
+        assert!(passes("size <= 3", "abc"));
+        assert!(passes("size in 1..3", "abc"));
+        assert!(!passes("size in 4..10", "abc"));
+        assert!(passes("size > 2", "abc"));
+        assert!(!passes("size >= 4", "abc"));
+        assert!(!passes("size > 18446744073709551615", "abc"));
+        assert!(passes("latency < 500ms", ""));
+        assert!(!passes("latency < 100ms", ""));
+    }
+
+    #[test]
+    fn invalid_checks_are_rejected() {
+        for check in [
+            "status",
+            "status 200",
+            "status in 600-500",
+            "body has ok",
+            "json $.a",
+            "json $[ == 1",
+            "header bad header",
+            "size 10",
+            "size in 10",
+            "latency > 1s",
+            "latency < soon",
+            "unknown thing",
+        ] {
+            assert!(Check::parse(check).is_err(), "{}", check);
+        }
+    }
+
+    #[test]
+    fn status_sets() {
+        let set = StatusSet::parse("200,201, 300-399,404").unwrap();
+        for code in [200, 201, 300, 350, 399, 404] {
+            assert!(
+                set.contains(StatusCode::from_u16(code).unwrap()),
+                "{}",
+                code
+            );
+        }
+        for code in [202, 299, 400, 500] {
+            assert!(
+                !set.contains(StatusCode::from_u16(code).unwrap()),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn default_status_set_is_2xx() {
+        assert_eq!(StatusSet::default(), StatusSet::parse("200-299").unwrap());
+        assert!(StatusSet::default().contains(StatusCode::NO_CONTENT));
+        assert!(!StatusSet::default().contains(StatusCode::NOT_MODIFIED));
+    }
+
+    #[test]
+    fn invalid_status_sets_are_rejected() {
+        for input in ["", "abc", "99", "1000", "299-200", "200-", "200,,201"] {
+            assert!(StatusSet::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn pass_rate() {
+        assert_eq!(CheckCount::default().pass_rate(), 0.0);
+        let count = CheckCount {
+            passed: 3,
+            failed: 1,
+        };
+        assert_eq!(count.pass_rate(), 75.0);
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::BTreeMap;
+
+use clap::{parser::ValueSource, ArgMatches};
+use serde::Deserialize;
+
+use crate::cli::Cli;
+
+// Options that exclude each other, the load model, the conditions, the body, the credentials
+// and the source of the requests
+const EXCLUSIVE_GROUPS: [&[&str]; 6] = [
+    &["duration", "rate", "stages"],
+    &["rate", "stages", "slo"],
+    &["slo", "fail_if"],
+    &["body", "body_file"],
+    &["basic_auth", "bearer", "bearer_file", "oauth2_token_url"],
+    &["url_file", "scenario"],
+];
+
+// Settings of a load test as defined in a config file
+// Every field is optional, unset fields keep the value of the command line
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    url: Option<String>,
+    method: Option<String>,
+    number: Option<u32>,
+    concurrency: Option<u32>,
+    duration: Option<String>,
+    rate: Option<f64>,
+    stages: Option<String>,
+    timeout: Option<u32>,
+    headers: Option<BTreeMap<String, String>>,
+    body: Option<String>,
+    body_file: Option<String>,
+    no_body: Option<bool>,
+    user_agent: Option<String>,
+    proxy: Option<String>,
+    basic_auth: Option<String>,
+    bearer: Option<String>,
+    bearer_file: Option<String>,
+    oauth2_token_url: Option<String>,
+    oauth2_client_id: Option<String>,
+    oauth2_client_secret: Option<String>,
+    oauth2_scope: Option<String>,
+    url_file: Option<String>,
+    scenario: Option<String>,
+    data: Option<String>,
+    data_mode: Option<String>,
+    disable_compression: Option<bool>,
+    disable_keepalive: Option<bool>,
+    disable_redirects: Option<bool>,
+    fail_if: Option<Vec<String>>,
+    check: Option<Vec<String>>,
+    expect_status: Option<String>,
+    schema: Option<String>,
+    schema_sample: Option<String>,
+    slo: Option<Vec<String>>,
+    search_start: Option<f64>,
+    search_step: Option<f64>,
+    search_max: Option<f64>,
+    no_tui: Option<bool>,
+    output: Option<String>,
+    output_file: Option<String>,
+    csv: Option<String>,
+    log_requests: Option<String>,
+}
+
+impl Settings {
+    /// Load the settings of a config file, with the named profile applied on top
+    pub fn load(path: &str, profile: Option<&str>) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config file {}: {}", path, e))?;
+        let mut table: toml::Table =
+            toml::from_str(&content).map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+
+        let profiles = match table.remove("profiles") {
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => {
+                return Err(format!(
+                    "Invalid config file {}: profiles must be a table",
+                    path
+                ))
+            }
+            None => toml::Table::new(),
+        };
+
+        let settings: Settings = table
+            .try_into()
+            .map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+
+        let Some(name) = profile else {
+            return Ok(settings);
+        };
+        let overlay: Settings = match profiles.get(name) {
+            Some(profile) => profile
+                .clone()
+                .try_into()
+                .map_err(|e| format!("Invalid profile {} in {}: {}", name, path, e))?,
+            None => {
+                let mut names: Vec<&String> = profiles.keys().collect();
+                names.sort();
+                return Err(format!(
+                    "Unknown profile {}, {} defines: {}",
+                    name,
+                    path,
+                    names
+                        .iter()
+                        .map(|name| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        };
+
+        Ok(settings.merge(overlay))
+    }
+
+    /// Overlay the settings of a profile, headers are merged
+    fn merge(self, overlay: Settings) -> Settings {
+        let headers = match (self.headers, overlay.headers) {
+            (Some(mut headers), Some(overlay)) => {
+                headers.extend(overlay);
+                Some(headers)
+            }
+            (headers, overlay) => overlay.or(headers),
+        };
+
+        Settings {
+            url: overlay.url.or(self.url),
+            method: overlay.method.or(self.method),
+            number: overlay.number.or(self.number),
+            concurrency: overlay.concurrency.or(self.concurrency),
+            duration: overlay.duration.or(self.duration),
+            rate: overlay.rate.or(self.rate),
+            stages: overlay.stages.or(self.stages),
+            timeout: overlay.timeout.or(self.timeout),
+            headers,
+            body: overlay.body.or(self.body),
+            body_file: overlay.body_file.or(self.body_file),
+            no_body: overlay.no_body.or(self.no_body),
+            user_agent: overlay.user_agent.or(self.user_agent),
+            proxy: overlay.proxy.or(self.proxy),
+            basic_auth: overlay.basic_auth.or(self.basic_auth),
+            bearer: overlay.bearer.or(self.bearer),
+            bearer_file: overlay.bearer_file.or(self.bearer_file),
+            oauth2_token_url: overlay.oauth2_token_url.or(self.oauth2_token_url),
+            oauth2_client_id: overlay.oauth2_client_id.or(self.oauth2_client_id),
+            oauth2_client_secret: overlay.oauth2_client_secret.or(self.oauth2_client_secret),
+            oauth2_scope: overlay.oauth2_scope.or(self.oauth2_scope),
+            url_file: overlay.url_file.or(self.url_file),
+            scenario: overlay.scenario.or(self.scenario),
+            data: overlay.data.or(self.data),
+            data_mode: overlay.data_mode.or(self.data_mode),
+            disable_compression: overlay.disable_compression.or(self.disable_compression),
+            disable_keepalive: overlay.disable_keepalive.or(self.disable_keepalive),
+            disable_redirects: overlay.disable_redirects.or(self.disable_redirects),
+            fail_if: overlay.fail_if.or(self.fail_if),
+            check: overlay.check.or(self.check),
+            expect_status: overlay.expect_status.or(self.expect_status),
+            schema: overlay.schema.or(self.schema),
+            schema_sample: overlay.schema_sample.or(self.schema_sample),
+            slo: overlay.slo.or(self.slo),
+            search_start: overlay.search_start.or(self.search_start),
+            search_step: overlay.search_step.or(self.search_step),
+            search_max: overlay.search_max.or(self.search_max),
+            no_tui: overlay.no_tui.or(self.no_tui),
+            output: overlay.output.or(self.output),
+            output_file: overlay.output_file.or(self.output_file),
+            csv: overlay.csv.or(self.csv),
+            log_requests: overlay.log_requests.or(self.log_requests),
+        }
+    }
+
+    /// Apply the settings to the arguments, options given on the command line take precedence
+    /// Headers of the config file are sent along with the ones of the command line
+    pub fn apply(self, args: &mut Cli, matches: &ArgMatches) {
+        // An option of a group given on the command line replaces the whole group of the config
+        // file, e.g. --stages drops its duration and rate
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let from_cli = |id: &str| {
+            given(id)
+                || EXCLUSIVE_GROUPS
+                    .iter()
+                    .filter(|group| group.contains(&id))
+                    .any(|group| group.iter().any(|id| given(id)))
+        };
+        fn set<T>(field: &mut T, value: Option<T>, from_cli: bool) {
+            if let (Some(value), false) = (value, from_cli) {
+                *field = value;
+            }
+        }
+
+        set(&mut args.url, self.url, from_cli("url"));
+        set(&mut args.method, self.method, from_cli("method"));
+        set(&mut args.number, self.number, from_cli("number"));
+        set(
+            &mut args.concurrency,
+            self.concurrency,
+            from_cli("concurrency"),
+        );
+        set(
+            &mut args.duration,
+            self.duration.map(Some),
+            from_cli("duration"),
+        );
+        set(&mut args.rate, self.rate.map(Some), from_cli("rate"));
+        set(&mut args.stages, self.stages.map(Some), from_cli("stages"));
+        set(&mut args.timeout, self.timeout, from_cli("timeout"));
+        set(&mut args.body, self.body.map(Some), from_cli("body"));
+        set(
+            &mut args.body_file,
+            self.body_file.map(Some),
+            from_cli("body_file"),
+        );
+        set(&mut args.no_body, self.no_body, from_cli("no_body"));
+        set(
+            &mut args.user_agent,
+            self.user_agent,
+            from_cli("user_agent"),
+        );
+        set(&mut args.proxy, self.proxy.map(Some), from_cli("proxy"));
+        set(
+            &mut args.basic_auth,
+            self.basic_auth.map(Some),
+            from_cli("basic_auth"),
+        );
+        set(&mut args.bearer, self.bearer.map(Some), from_cli("bearer"));
+        set(
+            &mut args.bearer_file,
+            self.bearer_file.map(Some),
+            from_cli("bearer_file"),
+        );
+        set(
+            &mut args.oauth2_token_url,
+            self.oauth2_token_url.map(Some),
+            from_cli("oauth2_token_url"),
+        );
+        set(
+            &mut args.oauth2_client_id,
+            self.oauth2_client_id.map(Some),
+            from_cli("oauth2_client_id"),
+        );
+        set(
+            &mut args.oauth2_client_secret,
+            self.oauth2_client_secret.map(Some),
+            from_cli("oauth2_client_secret"),
+        );
+        set(
+            &mut args.oauth2_scope,
+            self.oauth2_scope.map(Some),
+            from_cli("oauth2_scope"),
+        );
+        set(
+            &mut args.url_file,
+            self.url_file.map(Some),
+            from_cli("url_file"),
+        );
+        set(
+            &mut args.scenario,
+            self.scenario.map(Some),
+            from_cli("scenario"),
+        );
+        set(&mut args.data, self.data.map(Some), from_cli("data"));
+        set(&mut args.data_mode, self.data_mode, from_cli("data_mode"));
+        set(
+            &mut args.disable_compression,
+            self.disable_compression,
+            from_cli("disable_compression"),
+        );
+        set(
+            &mut args.disable_keepalive,
+            self.disable_keepalive,
+            from_cli("disable_keepalive"),
+        );
+        set(
+            &mut args.disable_redirects,
+            self.disable_redirects,
+            from_cli("disable_redirects"),
+        );
+        set(&mut args.fail_if, self.fail_if, from_cli("fail_if"));
+        set(&mut args.check, self.check, from_cli("check"));
+        set(
+            &mut args.expect_status,
+            self.expect_status,
+            from_cli("expect_status"),
+        );
+        set(&mut args.schema, self.schema.map(Some), from_cli("schema"));
+        set(
+            &mut args.schema_sample,
+            self.schema_sample,
+            from_cli("schema_sample"),
+        );
+        set(&mut args.slo, self.slo, from_cli("slo"));
+        set(
+            &mut args.search_start,
+            self.search_start,
+            from_cli("search_start"),
+        );
+        set(
+            &mut args.search_step,
+            self.search_step,
+            from_cli("search_step"),
+        );
+        set(
+            &mut args.search_max,
+            self.search_max.map(Some),
+            from_cli("search_max"),
+        );
+        set(&mut args.no_tui, self.no_tui, from_cli("no_tui"));
+        set(&mut args.output, self.output, from_cli("output"));
+        set(
+            &mut args.output_file,
+            self.output_file.map(Some),
+            from_cli("output_file"),
+        );
+        set(&mut args.csv, self.csv.map(Some), from_cli("csv"));
+        set(
+            &mut args.log_requests,
+            self.log_requests.map(Some),
+            from_cli("log_requests"),
+        );
+
+        // Headers of the command line come last and win over the config file
+        if let Some(headers) = self.headers {
+            let mut merged: Vec<String> = headers
+                .into_iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect();
+            merged.append(&mut args.headers);
+            args.headers = merged;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn apply(config: &str, cli: &[&str]) -> Cli {
+        let settings: Settings = toml::from_str(config).unwrap();
+        let matches = Cli::command()
+            .try_get_matches_from(std::iter::once("pepe").chain(cli.iter().copied()))
+            .unwrap();
+        let mut args = Cli::from_arg_matches(&matches).unwrap();
+        settings.apply(&mut args, &matches);
+        args
+    }
+
+    #[test]
+    fn url_file_is_read_from_the_config() {
+        let args = apply(r#"url_file = "urls.txt""#, &["--config", "pepe.toml"]);
+        assert_eq!(args.url_file.as_deref(), Some("urls.txt"));
+    }
+
+    #[test]
+    fn command_line_wins_over_the_config() {
+        let args = apply(
+            r#"url_file = "urls.txt"
+concurrency = 5"#,
+            &["--url-file", "other.txt"],
+        );
+        assert_eq!(args.url_file.as_deref(), Some("other.txt"));
+        assert_eq!(args.concurrency, 5);
+    }
+
+    #[test]
+    fn auth_of_the_command_line_replaces_the_auth_of_the_config() {
+        let args = apply(
+            r#"basic_auth = "user:pass""#,
+            &["--bearer", "token", "http://localhost"],
+        );
+        assert_eq!(args.basic_auth, None);
+        assert_eq!(args.bearer.as_deref(), Some("token"));
+    }
+
+    #[test]
+    fn body_of_the_command_line_replaces_the_body_of_the_config() {
+        let args = apply(
+            r#"body_file = "body.json""#,
+            &["-d", "{}", "http://localhost"],
+        );
+        assert_eq!(args.body_file, None);
+        assert_eq!(args.body.as_deref(), Some("{}"));
+    }
+
+    #[test]
+    fn scenario_of_the_command_line_replaces_the_url_file_of_the_config() {
+        let args = apply(
+            r#"url_file = "urls.txt""#,
+            &["--scenario", "steps.toml", "http://localhost"],
+        );
+        assert_eq!(args.url_file, None);
+        assert_eq!(args.scenario.as_deref(), Some("steps.toml"));
+    }
+
+    #[test]
+    fn conflicting_options_of_the_config_are_rejected() {
+        let mut args = apply(
+            r#"basic_auth = "user:pass"
+bearer = "token""#,
+            &["http://localhost"],
+        );
+        assert!(args.validate().is_err());
+
+        let mut args = apply(
+            r#"body = "{}"
+body_file = "body.json""#,
+            &["http://localhost"],
+        );
+        assert!(args.validate().is_err());
+    }
+
+    #[test]
+    fn load_model_of_the_command_line_replaces_the_load_model_of_the_config() {
+        let mut args = apply(
+            r#"duration = "10s"
+rate = 5.0"#,
+            &["--stages", "1s:2", "http://localhost"],
+        );
+        assert_eq!((args.duration.as_deref(), args.rate), (None, None));
+        assert!(args.validate().is_ok());
+
+        let mut args = apply(r#"stages = "1s:2""#, &["-z", "1s", "http://localhost"]);
+        assert_eq!(args.stages, None);
+        assert!(args.validate().is_ok());
+    }
+
+    #[test]
+    fn slo_and_fail_if_replace_each_other() {
+        let mut args = apply(
+            r#"slo = ["p99<300ms"]
+rate = 5.0"#,
+            &["--fail-if", "p99>1s", "http://localhost"],
+        );
+        assert!(args.slo.is_empty());
+        assert_eq!(args.fail_if, ["p99>1s"]);
+        assert!(args.validate().is_ok());
+
+        let mut args = apply(
+            r#"fail_if = ["p99>1s"]
+rate = 5.0"#,
+            &["--slo", "p99<300ms", "http://localhost"],
+        );
+        assert!(args.fail_if.is_empty());
+        assert_eq!(args.rate, None);
+        assert!(args.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Settings>(r#"urls = "urls.txt""#).is_err());
+    }
+}
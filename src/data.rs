@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use crate::template::Vars;
+
+// Order in which the rows of a data file are used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataMode {
+    // Rows in order, the test stops when every row was used
+    Sequential,
+    // Rows in order, starting over after the last one
+    Circular,
+    Random,
+}
+
+// DataFeed is the rows of a data file, each row binds its columns to template variables
+#[derive(Debug)]
+pub struct DataFeed {
+    path: String,
+    mode: DataMode,
+    rows: Vec<Arc<Vars>>,
+}
+
+impl DataMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "sequential" => Ok(DataMode::Sequential),
+            "circular" => Ok(DataMode::Circular),
+            "random" => Ok(DataMode::Random),
+            _ => Err(format!(
+                "Invalid data mode: {}, expected sequential, circular or random",
+                mode
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataMode::Sequential => "sequential",
+            DataMode::Circular => "circular",
+            DataMode::Random => "random",
+        }
+    }
+}
+
+impl DataFeed {
+    /// Load the rows of a CSV file with a header row, or of a JSON Lines file of objects
+    pub fn load(path: &str, mode: DataMode) -> Result<Self, String> {
+        let rows = if path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+            Self::read_jsonl(path)?
+        } else {
+            Self::read_csv(path)?
+        };
+        if rows.is_empty() {
+            return Err(format!("Data file {} has no rows", path));
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            mode,
+            rows: rows.into_iter().map(Arc::new).collect(),
+        })
+    }
+
+    fn read_csv(path: &str) -> Result<Vec<Vars>, String> {
+        let invalid = |e: csv::Error| format!("Invalid data file {}: {}", path, e);
+        let mut reader = csv::Reader::from_path(path).map_err(invalid)?;
+        let columns = reader.headers().map_err(invalid)?.clone();
+
+        reader
+            .records()
+            .map(|record| {
+                let record = record.map_err(invalid)?;
+                Ok(columns
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(column, value)| (column.to_string(), value.to_string()))
+                    .collect())
+            })
+            .collect()
+    }
+
+    fn read_jsonl(path: &str) -> Result<Vec<Vars>, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read data file {}: {}", path, e))?;
+
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let invalid =
+                    |e: String| format!("Invalid data file {}, line {}: {}", path, i + 1, e);
+                let row: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?;
+                Ok(row
+                    .into_iter()
+                    .map(|(key, value)| {
+                        // Strings are bound without their quotes, other values as JSON
+                        let value = match value {
+                            serde_json::Value::String(value) => value,
+                            value => value.to_string(),
+                        };
+                        (key, value)
+                    })
+                    .collect())
+            })
+            .collect()
+    }
+
+    /// Row of the request with the given index in the test
+    /// None once every row was used in sequential mode
+    pub fn row(&self, seq: u64) -> Option<Arc<Vars>> {
+        let index = match self.mode {
+            DataMode::Sequential => seq as usize,
+            DataMode::Circular => seq as usize % self.rows.len(),
+            DataMode::Random => fastrand::usize(..self.rows.len()),
+        };
+        self.rows.get(index).cloned()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} ({} rows, {})",
+            self.path,
+            self.rows.len(),
+            self.mode.as_str()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a data file of a test, its name is unique to the test
+    fn data_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("pepe-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn feed(rows: usize, mode: DataMode) -> DataFeed {
+        let content: String = (0..rows).map(|i| format!("{}\n", i)).collect();
+        DataFeed::load(
+            &data_file(&format!("{:?}.csv", mode), &format!("id\n{}", content)),
+            mode,
+        )
+        .unwrap()
+    }
+
+    fn id(feed: &DataFeed, seq: u64) -> Option<String> {
+        feed.row(seq).map(|row| row["id"].clone())
+    }
+
+    #[test]
+    fn csv_columns_are_bound_to_variables() {
+        let path = data_file(
+            "users.csv",
+            "user,password\nalice,secret\n\"bob, jr\",\"say \"\"hi\"\"\"\n",
+        );
+        let feed = DataFeed::load(&path, DataMode::Sequential).unwrap();
+
+        let row = feed.row(0).unwrap();
+        assert_eq!(row["user"], "alice");
+        assert_eq!(row["password"], "secret");
+        // Quoted fields keep their commas and escaped quotes
+        let row = feed.row(1).unwrap();
+        assert_eq!(row["user"], "bob, jr");
+        assert_eq!(row["password"], "say \"hi\"");
+    }
+
+    #[test]
+    fn jsonl_rows_must_be_objects() {
+        let path = data_file(
+            "users.jsonl",
+            "{\"user\": \"alice\", \"id\": 1}\n\n{\"user\": \"bob\", \"id\": 2}\n",
+        );
+        let feed = DataFeed::load(&path, DataMode::Sequential).unwrap();
+        let row = feed.row(1).unwrap();
+        assert_eq!(row["user"], "bob");
+        assert_eq!(row["id"], "2");
+
+        let path = data_file("invalid.jsonl", "{\"user\": \"alice\"}\n[\"bob\"]\n");
+        let error = DataFeed::load(&path, DataMode::Sequential).unwrap_err();
+        assert!(error.contains("line 2"), "{}", error);
+    }
+
+    #[test]
+    fn empty_files_are_rejected() {
+        for name in ["empty.csv", "header.csv", "empty.jsonl"] {
+            let content = if name == "header.csv" { "user\n" } else { "" };
+            let error =
+                DataFeed::load(&data_file(name, content), DataMode::Sequential).unwrap_err();
+            assert!(error.contains("has no rows"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rows_are_used_in_the_order_of_the_mode() {
+        let sequential = feed(3, DataMode::Sequential);
+        assert_eq!(id(&sequential, 0).as_deref(), Some("0"));
+        assert_eq!(id(&sequential, 2).as_deref(), Some("2"));
+        // The test stops once every row was used
+        assert_eq!(id(&sequential, 3), None);
+
+        let circular = feed(3, DataMode::Circular);
+        let ids: Vec<_> = (0..7).map(|seq| id(&circular, seq).unwrap()).collect();
+        assert_eq!(ids, ["0", "1", "2", "0", "1", "2", "0"]);
+
+        let random = feed(3, DataMode::Random);
+        for seq in 0..100 {
+            let id: usize = id(&random, seq).unwrap().parse().unwrap();
+            assert!(id < 3);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::path::Path;
+
+use bytes::Bytes;
+
+// Endpoint is one target of --url-file, picked by weight for every request
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub weight: u32,
+    pub method: String,
+    pub url: String,
+    pub body: Option<Bytes>,
+    // JSON Schema file the response bodies are validated against
+    pub schema: Option<String>,
+    // Method and target as written in the file, e.g. GET /search
+    pub label: String,
+}
+
+impl Endpoint {
+    /// Parse a line of an endpoints file: [weight] METHOD target [@body-file] [schema=file]
+    /// Relative targets are appended to the base URL, body and schema files are relative to the endpoints file
+    fn parse(line: &str, base_url: &str, dir: &Path) -> Result<Self, String> {
+        let mut words = split_words(line);
+
+        let weight = match words.first() {
+            Some(word) if word.chars().all(|c| c.is_ascii_digit()) => {
+                let weight = word
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid weight: {}", word))?;
+                words.remove(0);
+                weight
+            }
+            _ => 1,
+        };
+        if weight == 0 {
+            return Err("Weight must be greater than 0".to_string());
+        }
+
+        let usage = || "Expected [weight] METHOD target [@body-file] [schema=file]".to_string();
+        let (method, target, options) = match words.as_slice() {
+            [method, target, options @ ..] if options.len() <= 2 => (method, target, options),
+            _ => return Err(usage()),
+        };
+        let mut body_file = None;
+        let mut schema = None;
+        for option in options {
+            if let Some(file) = option.strip_prefix('@') {
+                body_file = Some(file);
+            } else if let Some(file) = option.strip_prefix("schema=") {
+                schema = Some(dir.join(file).to_string_lossy().into_owned());
+            } else {
+                return Err(usage());
+            }
+        }
+
+        let method = method.to_uppercase();
+        reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|_| format!("Invalid method: {}", method))?;
+
+        let url = resolve_url(base_url, target)?;
+        let body = body_file
+            .map(|body_file| read_body(dir, body_file))
+            .transpose()?;
+
+        Ok(Self {
+            weight,
+            label: format!("{} {}", method, target),
+            method,
+            url,
+            body,
+            schema,
+        })
+    }
+}
+
+/// URL of a target, relative targets are appended to the base URL
+pub fn resolve_url(base_url: &str, target: &str) -> Result<String, String> {
+    if target.starts_with("http://") || target.starts_with("https://") {
+        Ok(target.to_string())
+    } else if base_url.is_empty() {
+        Err(format!("Relative target {} requires a base URL", target))
+    } else {
+        Ok(format!(
+            "{}/{}",
+            base_url.trim_end_matches('/'),
+            target.trim_start_matches('/')
+        ))
+    }
+}
+
+/// Read a request body from a file, relative to the directory of the file that names it
+pub fn read_body(dir: &Path, body_file: &str) -> Result<Bytes, String> {
+    let path = dir.join(body_file);
+    std::fs::read(&path)
+        .map(Bytes::from)
+        .map_err(|e| format!("Cannot read body from {}: {}", path.display(), e))
+}
+
+/// Split a line on whitespace, except inside template expressions, e.g. {{randInt 1 5}}
+fn split_words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut depth = 0;
+
+    for (i, c) in line.char_indices() {
+        if line[i..].starts_with("{{") {
+            depth += 1;
+        } else if line[i..].starts_with("}}") && depth > 0 {
+            depth -= 1;
+        }
+
+        match (c.is_whitespace() && depth == 0, start) {
+            (true, Some(word_start)) => {
+                words.push(&line[word_start..i]);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        words.push(&line[word_start..]);
+    }
+    words
+}
+
+/// Load the endpoints of a file, one per line, blank lines and lines starting with # are skipped
+pub fn load(path: &str, base_url: &str) -> Result<Vec<Endpoint>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read endpoints file {}: {}", path, e))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let endpoints = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            Endpoint::parse(line, base_url, dir)
+                .map_err(|e| format!("Invalid endpoint in {}, line {}: {}", path, i + 1, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if endpoints.is_empty() {
+        return Err(format!("Endpoints file {} has no endpoints", path));
+    }
+    Ok(endpoints)
+}
+
+/// Pick the index of an endpoint at random, in proportion to its weight
+pub fn pick(endpoints: &[Endpoint]) -> usize {
+    let total: u64 = endpoints
+        .iter()
+        .map(|endpoint| endpoint.weight as u64)
+        .sum();
+    let mut point = fastrand::u64(..total);
+
+    for (i, endpoint) in endpoints.iter().enumerate() {
+        if point < endpoint.weight as u64 {
+            return i;
+        }
+        point -= endpoint.weight as u64;
+    }
+    endpoints.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Endpoint, String> {
+        Endpoint::parse(line, "http://localhost:8080/", Path::new("/tmp"))
+    }
+
+    #[test]
+    fn lines_with_and_without_weight() {
+        let endpoint = parse("70 get /search?q={{randChoice a b}} schema=search.json").unwrap();
+        assert_eq!(endpoint.weight, 70);
+        assert_eq!(endpoint.method, "GET");
+        assert_eq!(
+            endpoint.url,
+            "http://localhost:8080/search?q={{randChoice a b}}"
+        );
+        assert_eq!(endpoint.label, "GET /search?q={{randChoice a b}}");
+        assert_eq!(endpoint.schema.as_deref(), Some("/tmp/search.json"));
+        assert!(endpoint.body.is_none());
+
+        let endpoint = parse("DELETE https://other.example.com/items/1").unwrap();
+        assert_eq!(endpoint.weight, 1);
+        assert_eq!(endpoint.url, "https://other.example.com/items/1");
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        for line in [
+            "GET",
+            "0 GET /",
+            "99999999999 GET /",
+            "GET / body.json",
+            "G@T /",
+            "POST / @missing-body-file.json",
+        ] {
+            assert!(parse(line).is_err(), "{}", line);
+        }
+        assert!(Endpoint::parse("GET /", "", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn words_are_split_outside_of_templates() {
+        assert_eq!(
+            split_words("  GET /a?n={{randInt 1 5}}  @b.json "),
+            ["GET", "/a?n={{randInt 1 5}}", "@b.json"]
+        );
+    }
+
+    #[test]
+    fn endpoints_are_picked_by_weight() {
+        let endpoints = ["1 GET /a", "3 GET /b"]
+            .map(|line| parse(line).unwrap())
+            .to_vec();
+        let mut picks = [0; 2];
+        for _ in 0..4000 {
+            picks[pick(&endpoints)] += 1;
+        }
+        // 1000 and 3000 expected
+        assert!((800..1200).contains(&picks[0]), "{:?}", picks);
+    }
+}
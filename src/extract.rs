@@ -0,0 +1,196 @@
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, SET_COOKIE};
+use serde::Deserialize;
+use serde_json_path::JsonPath;
+
+use crate::response::Response;
+
+// Source of a captured value as written in a scenario file,
+// e.g. { json = "$.token" }, { regex = 'id="(\d+)"' } or { header = "Location" }
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Source {
+    Json(String),
+    Regex(String),
+    Header(String),
+}
+
+#[derive(Debug, Clone)]
+enum Extractor {
+    Json(JsonPath),
+    Regex(Regex),
+    Header(HeaderName),
+}
+
+// Capture stores a value of a response in a variable of the iteration
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub name: String,
+    extractor: Extractor,
+}
+
+impl Capture {
+    pub fn new(name: &str, source: Source) -> Result<Self, String> {
+        let extractor = match source {
+            Source::Json(path) => Extractor::Json(
+                JsonPath::parse(&path).map_err(|e| format!("Invalid JSONPath {}: {}", path, e))?,
+            ),
+            Source::Regex(pattern) => Extractor::Regex(
+                Regex::new(&pattern).map_err(|e| format!("Invalid regex {}: {}", pattern, e))?,
+            ),
+            Source::Header(name) => Extractor::Header(
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| format!("Invalid header name: {}", name))?,
+            ),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            extractor,
+        })
+    }
+
+    /// Value of the capture in a response, None when the response doesn't have it
+    fn extract(
+        &self,
+        headers: &HeaderMap,
+        body: &str,
+        json: &mut Option<Option<serde_json::Value>>,
+    ) -> Option<String> {
+        match &self.extractor {
+            Extractor::Json(path) => {
+                // The body is parsed once for all the JSON captures of a response
+                let json = json.get_or_insert_with(|| serde_json::from_str(body).ok());
+                let value = path.query(json.as_ref()?).first()?;
+                // Strings are captured without their quotes, other values as JSON
+                Some(match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+            }
+            Extractor::Regex(regex) => {
+                let captures = regex.captures(body)?;
+                // The first group when there is one, the whole match otherwise
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|value| value.as_str().to_string())
+            }
+            // Cookies are joined as the value of a Cookie header, e.g. a=1; b=2
+            Extractor::Header(name) if name == SET_COOKIE => {
+                let cookies: Vec<&str> = headers
+                    .get_all(SET_COOKIE)
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .filter_map(|cookie| cookie.split(';').next())
+                    .map(str::trim)
+                    .collect();
+                (!cookies.is_empty()).then(|| cookies.join("; "))
+            }
+            Extractor::Header(name) => headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+        }
+    }
+}
+
+/// Values of the captures found in a response
+pub fn extract_all(captures: &[Capture], response: &Response) -> Vec<(String, String)> {
+    let mut json = None;
+    captures
+        .iter()
+        .filter_map(|capture| {
+            capture
+                .extract(response.headers, response.body, &mut json)
+                .map(|value| (capture.name.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn capture(name: &str, source: &str) -> Capture {
+        Capture::new(name, toml::from_str::<Source>(source).unwrap()).unwrap()
+    }
+
+    fn extract(captures: &[Capture], headers: &HeaderMap, body: &str) -> Vec<(String, String)> {
+        extract_all(
+            captures,
+            &Response {
+                status: reqwest::StatusCode::OK,
+                headers,
+                body,
+                duration: std::time::Duration::ZERO,
+            },
+        )
+    }
+
+    #[test]
+    fn json_values() {
+        let captures = [
+            capture("token", r#"json = "$.token""#),
+            capture("id", r#"json = "$.items[0].id""#),
+            capture("missing", r#"json = "$.nope""#),
+        ];
+        let body = r#"{"token": "abc", "items": [{"id": 7}]}"#;
+        assert_eq!(
+            extract(&captures, &HeaderMap::new(), body),
+            [
+                ("token".to_string(), "abc".to_string()),
+                ("id".to_string(), "7".to_string())
+            ]
+        );
+        assert!(extract(&captures, &HeaderMap::new(), "not json").is_empty());
+    }
+
+    #[test]
+    fn regex_groups() {
+        let captures = [
+            capture("id", r#"regex = 'id="(\d+)"'"#),
+            capture("word", r#"regex = '[a-z]+'"#),
+        ];
+        assert_eq!(
+            extract(&captures, &HeaderMap::new(), r#"<a id="12">"#),
+            [
+                ("id".to_string(), "12".to_string()),
+                ("word".to_string(), "a".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn headers_and_cookies() {
+        let mut headers = HeaderMap::new();
+        headers.insert("location", HeaderValue::from_static("/items/3"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("a=1; Path=/"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("b=2; HttpOnly"));
+        let captures = [
+            capture("next", r#"header = "Location""#),
+            capture("cookies", r#"header = "Set-Cookie""#),
+            capture("missing", r#"header = "X-Nope""#),
+        ];
+        assert_eq!(
+            extract(&captures, &headers, ""),
+            [
+                ("next".to_string(), "/items/3".to_string()),
+                ("cookies".to_string(), "a=1; b=2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_sources_are_rejected() {
+        for source in [
+            r#"json = "$[""#,
+            r#"regex = "(""#,
+            r#"header = "bad header""#,
+        ] {
+            let source = toml::from_str::<Source>(source).unwrap();
+            assert!(Capture::new("x", source).is_err());
+        }
+    }
+}
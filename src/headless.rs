@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+use crate::cli::Cli;
+use crate::report;
+use crate::sink;
+use crate::stats::Results;
+use crate::utils::format_duration;
+use crate::{run_request, PepeError, RESULT_CHANNEL_CAPACITY};
+
+// Interval between two progress lines
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Run a test and collect its results, `progress` is called every second
+/// while the test is running
+pub async fn collect(args: &Cli, mut progress: impl FnMut(&Results)) -> Result<Results, PepeError> {
+    let (tx, mut rx) = mpsc::channel(RESULT_CHANNEL_CAPACITY);
+    let (sent_tx, mut sent_rx) = mpsc::channel(RESULT_CHANNEL_CAPACITY);
+    let tx = sink::tap(args, tx)?;
+
+    let start = Instant::now();
+    let handler = tokio::spawn({
+        let args = args.clone();
+        async move { run_request(&args, tx, sent_tx).await }
+    });
+
+    let mut results = Results::new(args);
+    let mut ticker = tokio::time::interval_at(
+        tokio::time::Instant::now() + PROGRESS_INTERVAL,
+        PROGRESS_INTERVAL,
+    );
+
+    // The result channel is closed once every request has completed
+    loop {
+        tokio::select! {
+            stat = rx.recv() => match stat {
+                Some(stat) => results.record(&stat),
+                None => break,
+            },
+            Some(sent) = sent_rx.recv() => results.record_sent(&sent),
+            _ = ticker.tick() => {
+                results.set_duration(start.elapsed());
+                progress(&results);
+            }
+        }
+    }
+    while let Ok(sent) = sent_rx.try_recv() {
+        results.record_sent(&sent);
+    }
+
+    handler
+        .await
+        .map_err(|e| PepeError::IoError(std::io::Error::other(e.to_string())))??;
+
+    results.set_duration(start.elapsed());
+    Ok(results)
+}
+
+/// Run a test without the dashboard, print progress lines to stderr
+/// and the report to stdout or --output-file
+pub async fn run(args: &Cli) -> Result<Results, PepeError> {
+    eprintln!("{}", describe(args));
+
+    let results = collect(args, |results| {
+        eprintln!("{}", progress_line(args, results))
+    })
+    .await?;
+
+    report::write(args, &results)?;
+    Ok(results)
+}
+
+fn describe(args: &Cli) -> String {
+    let load = match (args.load_profile(), args.rate) {
+        (Some(stages), _) => format!(
+            "{} stages, up to {}",
+            stages.stages.len(),
+            stages.format_target(stages.max_target())
+        ),
+        (None, Some(rate)) => format!("{} req/s", rate),
+        (None, None) => format!("{} concurrent", args.concurrency),
+    };
+    let length = match args.run_duration() {
+        Some(duration) => format_duration(duration),
+        None if args.is_scenario() => format!("{} iterations", args.number),
+        None => format!("{} requests", args.number),
+    };
+
+    format!("Running {} for {}, {}", args.target(), length, load)
+}
+
+fn progress_line(args: &Cli, results: &Results) -> String {
+    let stats = &results.stats;
+    let progress = match args.run_duration() {
+        Some(duration) => stats.duration.as_secs_f64() * 100.0 / duration.as_secs_f64(),
+        None => results.completed() as f64 * 100.0 / args.number.max(1) as f64,
+    };
+    let stage = results
+        .profile
+        .as_ref()
+        .map(|stages| {
+            let i = stages.index_at(stats.duration);
+            format!(" | stage {}/{}", i + 1, stages.stages.len())
+        })
+        .unwrap_or_default();
+
+    format!(
+        "[{:>6}] {:>3.0}% | {} requests, {} ok, {} failed, {} timeouts | {:.1} req/s | p99 {}ms{}",
+        format_duration(stats.duration),
+        progress.min(100.0),
+        stats.count,
+        stats.success,
+        stats.failed,
+        stats.timeouts,
+        stats.rps(),
+        stats.latencies.percentile(99.0),
+        stage
+    )
+}
@@ -1,30 +1,72 @@
 use std::io::stdout;
 use std::sync::Arc;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use crossterm::{
     cursor::Show,
     event::KeyCode,
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, LeaveAlternateScreen},
 };
-use tokio::sync::{mpsc, Semaphore};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
 
+use crate::check::Criteria;
 use crate::cli::Cli;
 use crate::request::Request;
 use crate::response::ResponseStats;
+use crate::scenario::StepRequest;
+use crate::stage::{StageUnit, Stages};
+use crate::stats::Stats;
+use crate::template::{Context, Vars};
 use crate::utils::resolve_dns;
 
+mod auth;
 mod cache;
+mod check;
 mod cli;
+mod config;
+mod data;
+mod endpoint;
+mod extract;
+mod headless;
+mod oauth2;
+mod report;
 mod request;
 mod response;
+mod scenario;
+mod schema;
+mod search;
+mod sink;
+mod stage;
+mod stats;
+mod template;
+mod threshold;
 mod ui;
 mod utils;
 
+// Results buffered between the workers and the consumer of a test, independent of -n
+// which is ignored by timed tests
+const RESULT_CHANNEL_CAPACITY: usize = 4096;
+
 #[derive(Debug, Clone)]
 struct Sent {
     count: usize,
+    queue_delay: Option<std::time::Duration>,
+}
+
+// Scheduling information of a dispatched request
+#[derive(Debug, Clone, Default)]
+struct Dispatch {
+    // Intended send time, only set when requests are sent at a rate
+    scheduled: Option<std::time::Instant>,
+    // Index of the load profile stage the request was sent in
+    stage: Option<usize>,
+    // Index of the request in the test
+    seq: u64,
+    // Template variables of the request, e.g. a row of --data
+    vars: Arc<Vars>,
+    // Index of the --url-file endpoint the request is sent to
+    endpoint: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -50,34 +92,198 @@ impl std::fmt::Display for PepeError {
 
 impl std::error::Error for PepeError {}
 
+/// Send a rendered request and check its response against the success criteria,
+/// the latency is measured from `start`. `inspect` is given the response with its body
+/// Bodies with an expected status are handed to the schema of the request, validated in the background
+async fn send_request(
+    client: &reqwest::Client,
+    request: &Request,
+    criteria: &Criteria,
+    start: std::time::Instant,
+    inspect: impl FnOnce(&response::Response),
+) -> ResponseStats {
+    let dns_times = resolve_dns(&request.url).await.unwrap_or_default();
+
+    let response = request.build(client).send().await;
+
+    let mut passed = Vec::new();
+    let mut stats = ResponseStats::from_response(response, start, dns_times, |response| {
+        passed = check::evaluate(&criteria.checks, response);
+        if let Some(schema) = request.schema.as_ref() {
+            if criteria.expect_status.contains(response.status) {
+                schema.submit(response.body);
+            }
+        }
+        inspect(response);
+    })
+    .await;
+    stats.expected_status = stats
+        .status_code
+        .is_some_and(|status_code| criteria.expect_status.contains(status_code));
+    stats.checks = passed;
+    stats.method = request.method().to_string();
+    stats.url = request.url.clone();
+    stats
+}
+
 async fn handle_request(
     client: Arc<reqwest::Client>,
     request: Request,
+    criteria: Arc<Criteria>,
     tx: mpsc::Sender<ResponseStats>,
     sent_tx: mpsc::Sender<Sent>,
     permit: tokio::sync::OwnedSemaphorePermit,
+    dispatch: Dispatch,
 ) {
+    let request = request.render(&Context {
+        seq: dispatch.seq,
+        vars: &dispatch.vars,
+    });
     let start = std::time::Instant::now();
-    let method = request.method();
+    let queue_delay = dispatch
+        .scheduled
+        .map(|scheduled| start.saturating_duration_since(scheduled));
 
-    let _ = sent_tx.send(Sent { count: 1 }).await;
+    let _ = sent_tx
+        .send(Sent {
+            count: 1,
+            queue_delay,
+        })
+        .await;
 
-    let dns_times = resolve_dns(&request.url).await.unwrap_or_default();
+    let mut stats = send_request(&client, &request, &criteria, start, |_| {}).await;
+    stats.queue_delay = queue_delay;
+    stats.stage = dispatch.stage;
+    stats.endpoint = dispatch.endpoint;
 
-    let response = if method == reqwest::Method::POST && request.body.is_some() {
-        client
-            .request(method, &request.url)
-            .body(request.body.unwrap())
-            .send()
-            .await
-    } else {
-        client.request(method, &request.url).send().await
-    };
+    drop(permit);
+    let _ = tx.send(stats).await;
+}
+
+/// Run the steps of the scenario in order, an iteration stops at the first failed step
+/// The variables of the iteration are shared by its steps, and set by their captures
+async fn handle_iteration(
+    client: Arc<reqwest::Client>,
+    steps: Arc<Vec<StepRequest>>,
+    criteria: Arc<Criteria>,
+    tx: mpsc::Sender<ResponseStats>,
+    sent_tx: mpsc::Sender<Sent>,
+    permit: tokio::sync::OwnedSemaphorePermit,
+    dispatch: Dispatch,
+) {
+    let iteration_start = std::time::Instant::now();
+    // Only the first step is scheduled, the others follow the previous response
+    let queue_delay = dispatch
+        .scheduled
+        .map(|scheduled| iteration_start.saturating_duration_since(scheduled));
+    let mut vars: Vars = (*dispatch.vars).clone();
+
+    for (i, step) in steps.iter().enumerate() {
+        let request = step.request.render(&Context {
+            seq: dispatch.seq,
+            vars: &vars,
+        });
+        let start = std::time::Instant::now();
+        let _ = sent_tx
+            .send(Sent {
+                count: 1,
+                queue_delay: queue_delay.filter(|_| i == 0),
+            })
+            .await;
+
+        let mut captured = Vec::new();
+        let mut stats = send_request(&client, &request, &criteria, start, |response| {
+            captured = extract::extract_all(&step.captures, response);
+        })
+        .await;
+        vars.extend(captured);
+        stats.queue_delay = queue_delay.filter(|_| i == 0);
+        stats.stage = dispatch.stage;
+        stats.endpoint = Some(i);
+
+        let failed = !stats.is_success();
+        let last = failed || i + 1 == steps.len();
+        if last {
+            stats.iteration = Some(iteration_start.elapsed());
+        }
 
-    let stats = ResponseStats::from_response(response, start, dns_times).await;
+        let _ = tx.send(stats).await;
+        if last {
+            break;
+        }
+    }
 
     drop(permit);
-    let _ = tx.send(stats).await;
+}
+
+// Users limits the concurrent requests of a users load profile to its target
+// The semaphore is sized for the peak of the profile and the permits above the
+// target are parked. A permit released by a finishing request goes to the
+// dispatcher first, which parks it while more permits are active than the target
+struct Users {
+    semaphore: Arc<Semaphore>,
+    parked: std::sync::Mutex<Vec<OwnedSemaphorePermit>>,
+    capacity: usize,
+}
+
+impl Users {
+    fn new(semaphore: Arc<Semaphore>, capacity: usize) -> Self {
+        Self {
+            semaphore,
+            parked: std::sync::Mutex::new(Vec::new()),
+            capacity,
+        }
+    }
+
+    fn target(&self, target: f64) -> usize {
+        (target.round() as usize).min(self.capacity)
+    }
+
+    /// Park free permits or release parked ones to move towards the target
+    /// Permits held by in-flight requests are parked by `admit` once they are released
+    fn adjust(&self, target: f64) {
+        let target = self.target(target);
+        let mut parked = self.parked.lock().unwrap();
+        let active = self.capacity - parked.len();
+        if active > target {
+            for _ in target..active {
+                match self.semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => parked.push(permit),
+                    Err(_) => break,
+                }
+            }
+        } else {
+            let released = parked.len().saturating_sub(target - active);
+            parked.truncate(released);
+        }
+    }
+
+    /// Permit of the next request, None when the permit is parked to ramp down
+    fn admit(&self, permit: OwnedSemaphorePermit, target: f64) -> Option<OwnedSemaphorePermit> {
+        let target = self.target(target);
+        let mut parked = self.parked.lock().unwrap();
+        if self.capacity - parked.len() > target {
+            parked.push(permit);
+            return None;
+        }
+        Some(permit)
+    }
+
+    /// Release the parked permits once the test is over
+    fn release(&self) {
+        self.parked.lock().unwrap().clear();
+    }
+}
+
+/// Ramp the number of concurrent requests along the stages of a load profile
+async fn ramp_users(stages: &Stages, users: &Users, start: tokio::time::Instant) {
+    let deadline = start + stages.total_duration();
+    let mut interval = tokio::time::interval(std::time::Duration::from_millis(100));
+
+    while tokio::time::Instant::now() < deadline {
+        interval.tick().await;
+        users.adjust(stages.target_at(start.elapsed()));
+    }
 }
 
 async fn run_request(
@@ -85,63 +291,225 @@ async fn run_request(
     tx: mpsc::Sender<ResponseStats>,
     sent_tx: mpsc::Sender<Sent>,
 ) -> Result<(Vec<ResponseStats>, std::time::Duration), PepeError> {
-    let request = args.request();
-    let client = Arc::new(request.build_client()?);
+    let requests = Arc::new(args.requests());
+    let steps = Arc::new(args.step_requests());
+    let criteria = Arc::new(args.criteria());
+    let client = Arc::new(requests[0].build_client()?);
+    let _token_refresh = args.oauth2.as_ref().map(|oauth2| oauth2.keep_fresh());
+    for schema in args.schemas.iter().flatten() {
+        schema.reset();
+    }
     let all_start = std::time::Instant::now();
-    let semaphore = Arc::new(Semaphore::new(args.concurrency as usize));
-
-    let handler = tokio::spawn({
-        let client = client.clone();
-        let tx = tx;
-        let sent_tx = sent_tx;
-        let number = args.number;
-
-        async move {
-            for _ in 0..number {
-                let semaphore = semaphore.clone();
-                let permit = semaphore
-                    .acquire_owned()
-                    .await
-                    .expect("Semaphore acquire failed");
-
-                tokio::spawn(handle_request(
+    let profile = args.load_profile();
+
+    // A users load profile ramps the permits, the semaphore is sized for its peak
+    let concurrency = match &profile {
+        Some(stages) if stages.unit == StageUnit::Users => stages.max_target().ceil() as u32,
+        _ => args.concurrency,
+    };
+    let semaphore = Arc::new(Semaphore::new(concurrency as usize));
+    let users = match &profile {
+        Some(stages) if stages.unit == StageUnit::Users => {
+            Some((stages, Users::new(semaphore.clone(), concurrency as usize)))
+        }
+        _ => None,
+    };
+    let start = tokio::time::Instant::now();
+    let deadline = args.run_duration().map(|duration| start + duration);
+    let number = args.number;
+    let rate = args.rate;
+    let no_vars = Arc::new(Vars::new());
+
+    let dispatcher = async {
+        let mut dispatched = 0;
+        let mut scheduled_offset = std::time::Duration::ZERO;
+        loop {
+            if deadline.is_none() && dispatched == number {
+                break;
+            }
+
+            // Open model, requests are scheduled at a fixed rate and the
+            // intended send time is kept to measure the queueing delay
+            let offset = match (&profile, rate) {
+                (Some(stages), _) if stages.unit == StageUnit::Rate => {
+                    match stages.send_time(dispatched as u64, scheduled_offset) {
+                        Some(offset) => Some(offset),
+                        None => break,
+                    }
+                }
+                (_, Some(rate)) => {
+                    Some(std::time::Duration::from_secs_f64(dispatched as f64 / rate))
+                }
+                _ => None,
+            };
+            let scheduled = match offset {
+                Some(offset) => {
+                    let scheduled = start + offset;
+                    if deadline.is_some_and(|deadline| scheduled >= deadline) {
+                        break;
+                    }
+                    scheduled_offset = offset;
+                    tokio::time::sleep_until(scheduled).await;
+                    Some(scheduled.into_std())
+                }
+                None => None,
+            };
+
+            let permit = match deadline {
+                // Time-bounded run, keep dispatching until the deadline
+                Some(deadline) => {
+                    if tokio::time::Instant::now() >= deadline {
+                        break;
+                    }
+                    match tokio::time::timeout_at(deadline, semaphore.clone().acquire_owned()).await
+                    {
+                        Ok(permit) => permit,
+                        Err(_) => break,
+                    }
+                }
+                None => semaphore.clone().acquire_owned().await,
+            }
+            .expect("Semaphore acquire failed");
+
+            let permit = match &users {
+                Some((stages, users)) => {
+                    match users.admit(permit, stages.target_at(start.elapsed())) {
+                        Some(permit) => permit,
+                        None => continue,
+                    }
+                }
+                None => permit,
+            };
+
+            // A sequential data feed ends the test after its last row
+            let vars = match &args.data_feed {
+                Some(feed) => match feed.row(dispatched as u64) {
+                    Some(row) => row,
+                    None => break,
+                },
+                None => no_vars.clone(),
+            };
+
+            let endpoint = (!args.endpoints.is_empty()).then(|| endpoint::pick(&args.endpoints));
+            let dispatch = Dispatch {
+                scheduled,
+                endpoint,
+                seq: dispatched as u64,
+                vars,
+                stage: profile
+                    .as_ref()
+                    .map(|stages| stages.index_at(start.elapsed())),
+            };
+
+            dispatched += 1;
+
+            if args.is_scenario() {
+                tokio::spawn(handle_iteration(
                     client.clone(),
-                    request.clone(),
+                    steps.clone(),
+                    criteria.clone(),
                     tx.clone(),
                     sent_tx.clone(),
                     permit,
+                    dispatch,
                 ));
+                continue;
             }
+
+            tokio::spawn(handle_request(
+                client.clone(),
+                requests[endpoint.unwrap_or(0)].clone(),
+                criteria.clone(),
+                tx.clone(),
+                sent_tx.clone(),
+                permit,
+                dispatch,
+            ));
         }
-    });
+    };
 
-    handler.await.map_err(|e| {
-        PepeError::IoError(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            e.to_string(),
-        ))
-    })?;
+    match &users {
+        Some((stages, users)) => {
+            // Park the permits above the initial target before dispatching
+            users.adjust(stages.target_at(std::time::Duration::ZERO));
+            tokio::join!(dispatcher, ramp_users(stages, users, start));
+            users.release();
+        }
+        None => dispatcher.await,
+    }
+
+    // Wait for in-flight requests to drain
+    let _ = semaphore.acquire_many(concurrency).await;
+    for schema in args.schemas.iter().flatten() {
+        schema.settle().await;
+    }
 
     Ok((Vec::new(), all_start.elapsed()))
 }
 
+/// Print the --fail-if thresholds breached by a test
+/// Returns whether the test passed
+fn check_thresholds(args: &Cli, stats: &Stats) -> bool {
+    let outcomes = threshold::evaluate(&args.fail_thresholds(), stats);
+    let breached: Vec<_> = outcomes.iter().filter(|outcome| outcome.breached).collect();
+
+    for outcome in &breached {
+        eprintln!(
+            "Threshold breached: {} (observed {})",
+            outcome.expression, outcome.observed
+        );
+    }
+    breached.is_empty()
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-    if let Err(e) = args.validate() {
+    if let Err(e) = args.load_config(&matches).and_then(|_| args.validate()) {
         eprintln!("{}", e);
         std::process::exit(1);
     }
 
+    // The first token is fetched before the test, which can't run without it
+    if let Some(oauth2) = &args.oauth2 {
+        if let Err(e) = oauth2.fetch().await {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if !args.slo.is_empty() {
+        search::run(&args).await?;
+        args.check_for_updates().await?;
+        return Ok(());
+    }
+
+    if args.is_headless() {
+        let results = headless::run(&args).await?;
+        if !check_thresholds(&args, &results.stats) {
+            std::process::exit(2);
+        }
+        return Ok(());
+    }
+
     enable_raw_mode()?;
     let mut stdout = stdout();
 
     let interrupted = Arc::new(tokio::sync::Notify::new());
+    let mut results;
 
     'main: loop {
-        let (tx, mut rx) = mpsc::channel(args.number as usize);
-        let (sent_tx, mut sent_rx) = mpsc::channel(args.number as usize);
+        let (tx, mut rx) = mpsc::channel(RESULT_CHANNEL_CAPACITY);
+        let (sent_tx, mut sent_rx) = mpsc::channel(RESULT_CHANNEL_CAPACITY);
+        let tx = match sink::tap(&args, tx) {
+            Ok(tx) => tx,
+            Err(e) => {
+                disable_raw_mode()?;
+                return Err(e.into());
+            }
+        };
 
         let handler = tokio::spawn({
             let args = args.clone();
@@ -152,6 +520,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         let result: Result<KeyCode, Box<dyn std::error::Error>> =
             dashboard.run(&mut rx, &mut sent_rx);
+        results = dashboard.results().clone();
 
         match result {
             Ok(KeyCode::Char('r')) => {
@@ -183,6 +552,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     )?;
 
     disable_raw_mode()?;
+
+    if args.has_report() {
+        report::write(&args, &results)?;
+    }
+    let passed = check_thresholds(&args, &results.stats);
+
     args.check_for_updates().await?;
+    if !passed {
+        std::process::exit(2);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn users_park_free_permits_above_the_target() {
+        let semaphore = Arc::new(Semaphore::new(10));
+        let users = Users::new(semaphore.clone(), 10);
+        users.adjust(4.0);
+        assert_eq!(semaphore.available_permits(), 4);
+
+        users.adjust(7.0);
+        assert_eq!(semaphore.available_permits(), 7);
+
+        users.release();
+        assert_eq!(semaphore.available_permits(), 10);
+    }
+
+    #[test]
+    fn users_park_released_permits_when_ramping_down() {
+        let semaphore = Arc::new(Semaphore::new(4));
+        let users = Users::new(semaphore.clone(), 4);
+        // Every permit is held by an in-flight request, none can be parked
+        let mut in_flight: Vec<_> = (0..4)
+            .map(|_| semaphore.clone().try_acquire_owned().unwrap())
+            .collect();
+        users.adjust(2.0);
+        assert_eq!(users.parked.lock().unwrap().len(), 0);
+
+        // The dispatcher gets the released permits and parks them down to the target
+        assert!(users.admit(in_flight.pop().unwrap(), 2.0).is_none());
+        assert!(users.admit(in_flight.pop().unwrap(), 2.0).is_none());
+        let permit = users.admit(in_flight.pop().unwrap(), 2.0);
+        assert!(permit.is_some());
+        assert_eq!(users.parked.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,665 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::auth::redact_header;
+use crate::cache::CacheCategory;
+use crate::check::CheckCount;
+use crate::cli::Cli;
+use crate::oauth2::TokenStats;
+use crate::schema::SchemaStats;
+use crate::stats::{Latencies, Results, Stats};
+use crate::threshold::{self, Outcome};
+use crate::PepeError;
+
+// Version of the JSON report schema, bumped on breaking changes
+const SCHEMA_VERSION: u32 = 1;
+
+const PERCENTILES: [f64; 8] = [10.0, 25.0, 50.0, 75.0, 90.0, 95.0, 99.0, 99.9];
+
+// Report is the machine-readable summary of a test
+// Latencies are in milliseconds, rates in percent
+#[derive(Serialize)]
+struct Report {
+    schema_version: u32,
+    pepe_version: &'static str,
+    parameters: Parameters,
+    summary: Summary,
+    latency: Latency,
+    // Only set when requests are sent at a rate
+    corrected_latency: Option<CorrectedLatency>,
+    status_codes: BTreeMap<u16, usize>,
+    // Requests that failed with an error, the most frequent kind first
+    errors: Vec<ErrorReport>,
+    cache: Cache,
+    dns: Dns,
+    // Fetches of the OAuth2 token, not part of the load
+    oauth2: Option<TokenStats>,
+    stages: Vec<StageReport>,
+    // Stats of the --url-file endpoints or of the --scenario steps
+    endpoints: Vec<EndpointReport>,
+    // Only set when a scenario runs
+    iterations: Option<Iterations>,
+    checks: Vec<CheckReport>,
+    schemas: Vec<SchemaReport>,
+    thresholds: Vec<Outcome>,
+}
+
+#[derive(Serialize)]
+struct Parameters {
+    url: String,
+    method: String,
+    concurrency: u32,
+    // Only set when the test is not time-bounded
+    requests: Option<u32>,
+    duration_ms: Option<u128>,
+    rate: Option<f64>,
+    stages: Option<String>,
+    timeout_s: u32,
+    // Header values holding credentials are redacted
+    headers: Vec<String>,
+    auth: Option<String>,
+    data: Option<String>,
+    expect_status: String,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    requests: usize,
+    sent: usize,
+    success: usize,
+    failed: usize,
+    timeouts: usize,
+    error_rate: f64,
+    duration_ms: u128,
+    rps: f64,
+    data_transfer_bytes: u64,
+    throughput_bytes_per_sec: f64,
+}
+
+#[derive(Serialize)]
+struct Latency {
+    min: u64,
+    max: u64,
+    avg: u64,
+    std_dev: u64,
+    percentiles: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+struct CorrectedLatency {
+    percentiles: BTreeMap<String, u64>,
+    late_sends: usize,
+    max_queue_delay: u64,
+}
+
+#[derive(Serialize)]
+struct Cache {
+    hit: usize,
+    miss: usize,
+    unknown: usize,
+    hit_rate: f64,
+}
+
+#[derive(Serialize)]
+struct Dns {
+    avg_lookup_ms: u128,
+    avg_resolution_ms: u128,
+}
+
+#[derive(Serialize)]
+struct StageReport {
+    stage: String,
+    requests: usize,
+    success: usize,
+    failed: usize,
+    timeouts: usize,
+    avg: u64,
+    p99: u64,
+    rps: f64,
+}
+
+#[derive(Serialize)]
+struct EndpointReport {
+    endpoint: String,
+    requests: usize,
+    success: usize,
+    failed: usize,
+    timeouts: usize,
+    error_rate: f64,
+    avg: u64,
+    max: u64,
+    percentiles: BTreeMap<String, u64>,
+    data_transfer_bytes: u64,
+    rps: f64,
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    kind: &'static str,
+    count: usize,
+    samples: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SchemaReport {
+    endpoint: String,
+    schema: String,
+    #[serde(flatten)]
+    stats: SchemaStats,
+    violation_rate: f64,
+}
+
+#[derive(Serialize)]
+struct CheckReport {
+    check: String,
+    #[serde(flatten)]
+    count: CheckCount,
+    pass_rate: f64,
+}
+
+#[derive(Serialize)]
+struct Iterations {
+    count: usize,
+    failed: usize,
+    avg: u64,
+    percentiles: BTreeMap<String, u64>,
+}
+
+impl Report {
+    fn new(args: &Cli, results: &Results) -> Self {
+        let stats = &results.stats;
+        let run_duration = args.run_duration();
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            pepe_version: env!("CARGO_PKG_VERSION"),
+            parameters: Parameters {
+                url: args.url.clone(),
+                method: args.method.clone(),
+                concurrency: args.concurrency,
+                requests: run_duration.is_none().then_some(args.number),
+                duration_ms: run_duration.map(|duration| duration.as_millis()),
+                rate: args.rate,
+                stages: args.stages.clone(),
+                timeout_s: args.timeout,
+                headers: args
+                    .headers
+                    .iter()
+                    .map(|header| redact_header(header))
+                    .collect(),
+                auth: args.auth().ok().flatten().map(|auth| auth.describe()),
+                data: args.data_feed.as_ref().map(|feed| feed.describe()),
+                expect_status: args.expect_status.clone(),
+            },
+            summary: Summary {
+                requests: stats.count,
+                sent: stats.sent,
+                success: stats.success,
+                failed: stats.failed,
+                timeouts: stats.timeouts,
+                error_rate: stats.error_rate(),
+                duration_ms: stats.duration.as_millis(),
+                rps: stats.rps(),
+                data_transfer_bytes: stats.data_transfer,
+                throughput_bytes_per_sec: stats.throughput(),
+            },
+            latency: Latency {
+                min: stats.min(),
+                max: stats.max(),
+                avg: stats.avg(),
+                std_dev: stats.std_dev(),
+                percentiles: percentiles(&stats.latencies),
+            },
+            corrected_latency: args.is_open_model().then(|| CorrectedLatency {
+                percentiles: percentiles(&stats.corrected_latencies),
+                late_sends: stats.late_sends,
+                max_queue_delay: stats.max_queue_delay,
+            }),
+            status_codes: stats
+                .status_codes
+                .iter()
+                .map(|(status_code, count)| (status_code.as_u16(), *count))
+                .collect(),
+            errors: stats
+                .sorted_errors()
+                .into_iter()
+                .map(|(kind, count)| ErrorReport {
+                    kind: kind.as_str(),
+                    count: count.count,
+                    samples: count.samples.clone(),
+                })
+                .collect(),
+            cache: Cache {
+                hit: cache_count(stats, CacheCategory::Hit),
+                miss: cache_count(stats, CacheCategory::Miss),
+                unknown: cache_count(stats, CacheCategory::Unknown),
+                hit_rate: stats.cache_hit_rate(),
+            },
+            dns: Dns {
+                avg_lookup_ms: stats.avg_dns_lookup(),
+                avg_resolution_ms: stats.avg_dns_resolution(),
+            },
+            oauth2: args.oauth2.as_ref().map(|oauth2| oauth2.stats()),
+            stages: results
+                .profile
+                .iter()
+                .flat_map(|stages| {
+                    results
+                        .stages
+                        .iter()
+                        .enumerate()
+                        .map(|(i, stats)| StageReport {
+                            stage: stages.format_stage(i),
+                            requests: stats.count,
+                            success: stats.success,
+                            failed: stats.failed,
+                            timeouts: stats.timeouts,
+                            avg: stats.avg(),
+                            p99: stats.latencies.percentile(99.0),
+                            rps: stats.rps(),
+                        })
+                })
+                .collect(),
+            endpoints: args
+                .endpoint_labels()
+                .into_iter()
+                .zip(results.endpoints.iter())
+                .map(|(endpoint, stats)| EndpointReport {
+                    endpoint,
+                    requests: stats.count,
+                    success: stats.success,
+                    failed: stats.failed,
+                    timeouts: stats.timeouts,
+                    error_rate: stats.error_rate(),
+                    avg: stats.avg(),
+                    max: stats.max(),
+                    percentiles: percentiles(&stats.latencies),
+                    data_transfer_bytes: stats.data_transfer,
+                    rps: stats.rps(),
+                })
+                .collect(),
+            iterations: results.scenario.then(|| Iterations {
+                count: stats.iterations.len(),
+                failed: stats.failed_iterations,
+                avg: stats.iterations.avg(),
+                percentiles: percentiles(&stats.iterations),
+            }),
+            checks: args
+                .checks()
+                .into_iter()
+                .enumerate()
+                .map(|(i, check)| {
+                    let count = stats.checks.get(i).cloned().unwrap_or_default();
+                    CheckReport {
+                        check: check.expression,
+                        pass_rate: count.pass_rate(),
+                        count,
+                    }
+                })
+                .collect(),
+            schemas: args
+                .labeled_schemas()
+                .into_iter()
+                .map(|(endpoint, schema)| {
+                    let stats = schema.stats();
+                    SchemaReport {
+                        endpoint,
+                        schema: schema.path.clone(),
+                        violation_rate: stats.violation_rate(),
+                        stats,
+                    }
+                })
+                .collect(),
+            thresholds: threshold::evaluate(&args.fail_thresholds(), stats),
+        }
+    }
+}
+
+fn percentiles(latencies: &Latencies) -> BTreeMap<String, u64> {
+    PERCENTILES
+        .iter()
+        .map(|&p| (format!("p{}", p), latencies.percentile(p)))
+        .collect()
+}
+
+fn cache_count(stats: &Stats, category: CacheCategory) -> usize {
+    *stats.cache_categories.get(&category).unwrap_or(&0)
+}
+
+/// Write the report of a test in the format of --output,
+/// to --output-file when set and to stdout otherwise
+pub fn write(args: &Cli, results: &Results) -> Result<(), PepeError> {
+    let report = match args.output.as_str() {
+        "json" => {
+            serde_json::to_string_pretty(&Report::new(args, results))
+                .map_err(|e| PepeError::IoError(e.into()))?
+                + "\n"
+        }
+        _ => text(args, results),
+    };
+
+    match &args.output_file {
+        Some(path) => std::fs::write(path, report).map_err(PepeError::IoError),
+        None => {
+            print!("{}", report);
+            Ok(())
+        }
+    }
+}
+
+/// Plain-text summary of a test
+fn text(args: &Cli, results: &Results) -> String {
+    let stats = &results.stats;
+    let mut lines = vec![
+        "Summary".to_string(),
+        format!("  Requests:       {}", stats.count),
+        format!("  Success:        {}", stats.success),
+        format!("  Failed:         {}", stats.failed),
+        format!("  Timeouts:       {}", stats.timeouts),
+        format!("  Duration:       {:.2}s", stats.duration.as_secs_f64()),
+        format!("  Requests/sec:   {:.2}", stats.rps()),
+        format!(
+            "  Data:           {:.2}kb ({:.2}kb/s)",
+            stats.data_transfer as f64 / 1024.0,
+            stats.throughput() / 1024.0
+        ),
+    ];
+
+    let latencies = &stats.latencies;
+    lines.push("\nLatency".to_string());
+    lines.push(format!("  Min:            {}ms", stats.min()));
+    lines.push(format!("  Max:            {}ms", stats.max()));
+    lines.push(format!("  Avg:            {}ms", stats.avg()));
+    lines.push(format!("  Std Dev:        {}ms", stats.std_dev()));
+    for p in PERCENTILES {
+        lines.push(format!(
+            "  {:<15} {}ms",
+            format!("P{}:", p),
+            latencies.percentile(p)
+        ));
+    }
+
+    if args.is_open_model() {
+        let corrected = &stats.corrected_latencies;
+        lines.push("\nCorrected latency (from the intended send time)".to_string());
+        lines.push(format!(
+            "  P99:            {}ms",
+            corrected.percentile(99.0)
+        ));
+        lines.push(format!(
+            "  P99.9:          {}ms",
+            corrected.percentile(99.9)
+        ));
+        lines.push(format!(
+            "  Late sends:     {} (max {}ms behind)",
+            stats.late_sends, stats.max_queue_delay
+        ));
+    }
+
+    lines.push("\nStatus codes".to_string());
+    let mut status_codes: Vec<_> = stats.status_codes.iter().collect();
+    status_codes.sort();
+    for (status_code, count) in status_codes {
+        lines.push(format!(
+            "  {:<15} {}",
+            format!("{}:", status_code.as_u16()),
+            count
+        ));
+    }
+    if stats.timeouts > 0 {
+        lines.push(format!("  {:<15} {}", "Timeout:", stats.timeouts));
+    }
+
+    let errors = stats.sorted_errors();
+    if !errors.is_empty() {
+        lines.push("\nErrors".to_string());
+        for (kind, count) in errors {
+            lines.push(format!(
+                "  {:<20} {}",
+                format!("{}:", kind.label()),
+                count.count
+            ));
+            for sample in &count.samples {
+                lines.push(format!("    {}", sample));
+            }
+        }
+    }
+
+    lines.push("\nCache".to_string());
+    for category in [
+        CacheCategory::Hit,
+        CacheCategory::Miss,
+        CacheCategory::Unknown,
+    ] {
+        lines.push(format!(
+            "  {:<15} {}",
+            format!("{:?}:", category),
+            cache_count(stats, category)
+        ));
+    }
+    lines.push(format!("  Hit rate:       {:.2}%", stats.cache_hit_rate()));
+
+    lines.push("\nDNS".to_string());
+    lines.push(format!("  Avg lookup:     {}ms", stats.avg_dns_lookup()));
+    lines.push(format!(
+        "  Avg resolution: {}ms",
+        stats.avg_dns_resolution()
+    ));
+
+    if let Some(oauth2) = &args.oauth2 {
+        let token = oauth2.stats();
+        lines.push("\nOAuth2 token".to_string());
+        lines.push(format!("  Fetches:        {}", token.fetches));
+        lines.push(format!("  Failures:       {}", token.failures));
+        lines.push(format!("  Avg latency:    {}ms", token.avg_latency_ms));
+        lines.push(format!("  Max latency:    {}ms", token.max_latency_ms));
+        if let Some(error) = &token.last_error {
+            lines.push(format!("  Last error:     {}", error));
+        }
+    }
+
+    if let Some(stages) = &results.profile {
+        lines.push("\nStages".to_string());
+        lines.push(format!(
+            "  {:>2}  {:<20}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}",
+            "#", "Stage", "Requests", "OK", "Errors", "Avg", "P99", "RPS"
+        ));
+        for (i, stats) in results.stages.iter().enumerate() {
+            lines.push(format!(
+                "  {:>2}  {:<20}  {:>8}  {:>8}  {:>8}  {:>6}ms  {:>6}ms  {:>8.1}",
+                i + 1,
+                stages.format_stage(i),
+                stats.count,
+                stats.success,
+                stats.failed + stats.timeouts,
+                stats.avg(),
+                stats.latencies.percentile(99.0),
+                stats.rps()
+            ));
+        }
+    }
+
+    if results.scenario {
+        let iterations = &stats.iterations;
+        lines.push("\nIterations".to_string());
+        lines.push(format!("  Completed:      {}", iterations.len()));
+        lines.push(format!("  Failed:         {}", stats.failed_iterations));
+        for p in [50.0, 95.0, 99.0] {
+            lines.push(format!(
+                "  {:<15} {}ms",
+                format!("P{}:", p),
+                iterations.percentile(p)
+            ));
+        }
+    }
+
+    if !results.endpoints.is_empty() {
+        let title = if results.scenario { "Step" } else { "Endpoint" };
+        lines.push(format!("\n{}s", title));
+        lines.push(format!(
+            "  {:<30}  {:>8}  {:>8}  {:>7}  {:>8}  {:>8}  {:>8}  {:>10}",
+            title, "Requests", "Errors", "Error%", "Avg", "P95", "P99", "Data"
+        ));
+        for (label, stats) in args.endpoint_labels().iter().zip(&results.endpoints) {
+            let latencies = &stats.latencies;
+            lines.push(format!(
+                "  {:<30}  {:>8}  {:>8}  {:>6.2}%  {:>6}ms  {:>6}ms  {:>6}ms  {:>8.2}kb",
+                label,
+                stats.count,
+                stats.failed + stats.timeouts,
+                stats.error_rate(),
+                stats.avg(),
+                latencies.percentile(95.0),
+                latencies.percentile(99.0),
+                stats.data_transfer as f64 / 1024.0
+            ));
+        }
+    }
+
+    let checks = args.checks();
+    if !checks.is_empty() {
+        lines.push("\nChecks".to_string());
+        for (i, check) in checks.iter().enumerate() {
+            let count = stats.checks.get(i).cloned().unwrap_or_default();
+            lines.push(format!(
+                "  {} {} ({} passed, {} failed, {:.2}%)",
+                if count.failed > 0 { "FAIL" } else { "ok  " },
+                check.expression,
+                count.passed,
+                count.failed,
+                count.pass_rate()
+            ));
+        }
+    }
+
+    let schemas = args.labeled_schemas();
+    if !schemas.is_empty() {
+        lines.push("\nSchema validation".to_string());
+        for (endpoint, schema) in schemas {
+            let stats = schema.stats();
+            lines.push(format!(
+                "  {} ({}): {} validated, {} invalid ({:.2}%), {} skipped",
+                endpoint,
+                schema.path,
+                stats.validated,
+                stats.violations,
+                stats.violation_rate(),
+                stats.skipped
+            ));
+            for sample in &stats.samples {
+                lines.push(format!("    {}: {}", sample.path, sample.message));
+            }
+        }
+    }
+
+    let outcomes = threshold::evaluate(&args.fail_thresholds(), stats);
+    if !outcomes.is_empty() {
+        lines.push("\nThresholds".to_string());
+        for outcome in outcomes {
+            lines.push(format!(
+                "  {} {} (observed {})",
+                if outcome.breached { "FAIL" } else { "ok  " },
+                outcome.expression,
+                outcome.observed
+            ));
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::ResponseStats;
+    use clap::{CommandFactory, FromArgMatches};
+    use std::time::Duration;
+
+    fn args(args: &[&str]) -> Cli {
+        let matches = Cli::command()
+            .try_get_matches_from(std::iter::once("pepe").chain(args.iter().copied()))
+            .unwrap();
+        let mut args = Cli::from_arg_matches(&matches).unwrap();
+        args.validate().unwrap();
+        args
+    }
+
+    /// Check the fields of a JSON object, serde_json sorts them by name
+    fn assert_fields(value: &serde_json::Value, fields: &[&str]) {
+        let mut fields = fields.to_vec();
+        fields.sort_unstable();
+        let keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(keys, fields);
+    }
+
+    #[test]
+    fn json_report_keeps_its_schema() {
+        let args = args(&["-n", "1", "-o", "json", "http://localhost/items"]);
+        let mut results = Results::new(&args);
+        results.record(&ResponseStats {
+            method: "GET".to_string(),
+            url: "http://localhost/items".to_string(),
+            duration: Duration::from_millis(12),
+            status_code: Some(reqwest::StatusCode::OK),
+            expected_status: true,
+            content_length: Some(42),
+            ..Default::default()
+        });
+
+        let report = serde_json::to_value(Report::new(&args, &results)).unwrap();
+        // Fields are only added within a schema version, a removed or renamed field bumps it
+        assert_eq!(report["schema_version"], 1);
+        assert_fields(
+            &report,
+            &[
+                "schema_version",
+                "pepe_version",
+                "parameters",
+                "summary",
+                "latency",
+                "corrected_latency",
+                "status_codes",
+                "errors",
+                "cache",
+                "dns",
+                "oauth2",
+                "stages",
+                "endpoints",
+                "iterations",
+                "checks",
+                "schemas",
+                "thresholds",
+            ],
+        );
+        assert_fields(
+            &report["summary"],
+            &[
+                "requests",
+                "sent",
+                "success",
+                "failed",
+                "timeouts",
+                "error_rate",
+                "duration_ms",
+                "rps",
+                "data_transfer_bytes",
+                "throughput_bytes_per_sec",
+            ],
+        );
+        assert_fields(
+            &report["latency"],
+            &["min", "max", "avg", "std_dev", "percentiles"],
+        );
+        assert_eq!(report["summary"]["requests"], 1);
+        assert_eq!(report["summary"]["success"], 1);
+        assert_eq!(report["status_codes"]["200"], 1);
+        assert_eq!(report["latency"]["percentiles"]["p50"], 12);
+        assert_eq!(report["parameters"]["url"], "http://localhost/items");
+    }
+}
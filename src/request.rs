@@ -1,7 +1,15 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use reqwest::{header::USER_AGENT, Proxy};
+use bytes::Bytes;
+use reqwest::{
+    header::{AUTHORIZATION, USER_AGENT},
+    Proxy,
+};
 
+use crate::auth::Auth;
+use crate::schema::Schema;
+use crate::template::{parse_dynamic, Context, Template};
 use crate::PepeError;
 
 #[derive(Debug, Clone)]
@@ -12,46 +20,169 @@ pub struct RequestSettings {
     pub disable_redirects: bool,
     pub proxy: Option<String>,
     pub user_agent: String,
+    pub auth: Option<Auth>,
+}
+
+// Templates of the parts of a request that change for every request
+#[derive(Debug, Default)]
+struct Templates {
+    url: Option<Template>,
+    headers: HashMap<String, Template>,
+    body: Option<Template>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Request {
     pub url: String,
     pub method: String,
-    pub body: Option<String>,
+    pub body: Option<Bytes>,
     pub headers: HashMap<String, String>,
     pub settings: RequestSettings,
+    // JSON Schema a sample of the response bodies is validated against
+    pub schema: Option<Arc<Schema>>,
+    templates: Arc<Templates>,
+    // Headers without templates, parsed once and sent with every request
+    static_headers: Arc<reqwest::header::HeaderMap>,
+}
+
+/// Name and value of a header, e.g. 'Accept: application/json'
+fn split_header(header: &str) -> Option<(&str, &str)> {
+    header
+        .split_once(':')
+        .map(|(name, value)| (name.trim(), value.trim()))
+}
+
+/// Check that a header can be sent, requests are built from valid headers only
+pub fn check_header(header: &str) -> Result<(), String> {
+    let (name, value) = split_header(header)
+        .ok_or_else(|| format!("Invalid header, expected 'Name: value': {}", header))?;
+    check_header_field(name, value)
+}
+
+/// Check the name and the value of a header, e.g. a header of a scenario step
+pub fn check_header_field(name: &str, value: &str) -> Result<(), String> {
+    reqwest::header::HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("Invalid header name: {}", name))?;
+    reqwest::header::HeaderValue::from_str(value)
+        .map_err(|_| format!("Invalid value of header {}: {}", name, value))?;
+    Ok(())
+}
+
+impl Templates {
+    fn new(url: &str, headers: &HashMap<String, String>, body: Option<&Bytes>) -> Self {
+        // Templates are validated with the arguments, invalid ones are sent as is
+        let dynamic = |input: &str| parse_dynamic(input).ok().flatten();
+
+        Self {
+            url: dynamic(url),
+            headers: headers
+                .iter()
+                .filter_map(|(name, value)| dynamic(value).map(|template| (name.clone(), template)))
+                .collect(),
+            // Binary bodies are never templates
+            body: body
+                .and_then(|body| std::str::from_utf8(body).ok())
+                .and_then(dynamic),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.url.is_none() && self.headers.is_empty() && self.body.is_none()
+    }
 }
 
 impl Request {
     pub fn new(
         url: String,
         method: String,
-        body: Option<String>,
+        body: Option<Bytes>,
         headers: Vec<String>,
         settings: RequestSettings,
     ) -> Self {
         let mut header_map = HashMap::new();
-        for header in headers {
-            let parts: Vec<&str> = header.splitn(2, ':').collect();
-            if parts.len() == 2 {
-                header_map.insert(parts[0].trim().to_string(), parts[1].trim().to_string());
+        for header in &headers {
+            if let Some((name, value)) = split_header(header) {
+                header_map.insert(name.to_string(), value.to_string());
             }
         }
 
+        let templates = Arc::new(Templates::new(&url, &header_map, body.as_ref()));
+        let mut static_headers = Self::parse_headers(
+            &header_map
+                .iter()
+                .filter(|(name, _)| !templates.headers.contains_key(*name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        );
+        // Credentials of the settings win over an Authorization header
+        if settings.auth.is_some() {
+            static_headers.remove(AUTHORIZATION);
+        }
+
         Self {
             url,
             method,
             body,
             headers: header_map,
             settings,
+            schema: None,
+            templates,
+            static_headers: Arc::new(static_headers),
         }
     }
 
+    /// Send the body as is, e.g. a body read from a file, its {{...}} are not rendered
+    pub fn raw_body(mut self) -> Self {
+        self.templates = Arc::new(Templates::new(&self.url, &self.headers, None));
+        self
+    }
+
+    /// Render the templates of the request for one request of the test
+    pub fn render(&self, context: &Context) -> Request {
+        let mut request = self.clone();
+        if self.templates.is_empty() {
+            return request;
+        }
+
+        if let Some(url) = &self.templates.url {
+            request.url = url.render(context);
+        }
+        for (name, template) in &self.templates.headers {
+            request
+                .headers
+                .insert(name.clone(), template.render(context));
+        }
+        if let Some(body) = &self.templates.body {
+            request.body = Some(Bytes::from(body.render(context)));
+        }
+        request
+    }
+
     pub fn method(&self) -> reqwest::Method {
         reqwest::Method::from_bytes(self.method.as_bytes()).unwrap_or(reqwest::Method::GET)
     }
 
+    /// Build the request to send with the client, the body is sent with any method
+    pub fn build(&self, client: &reqwest::Client) -> reqwest::RequestBuilder {
+        // Headers belong to the request, the client is shared by the endpoints and steps
+        let mut builder = client
+            .request(self.method(), &self.url)
+            .headers(self.static_headers.as_ref().clone());
+        if let Some(header) = self.settings.auth.as_ref().and_then(Auth::request_header) {
+            builder = builder.header(AUTHORIZATION, header);
+        }
+        // Headers with templates change for every request
+        for name in self.templates.headers.keys() {
+            if let Some(value) = self.headers.get(name) {
+                builder = builder.header(name, value);
+            }
+        }
+        match &self.body {
+            Some(body) => builder.body(body.clone()),
+            None => builder,
+        }
+    }
+
     fn parse_headers(headers: &HashMap<String, String>) -> reqwest::header::HeaderMap {
         let mut request_headers = reqwest::header::HeaderMap::new();
         for (name, value) in headers {
@@ -63,8 +194,9 @@ impl Request {
         request_headers
     }
 
+    /// Build the client of the test, it only holds the settings shared by every request
     pub fn build_client(&self) -> Result<reqwest::Client, PepeError> {
-        let mut request_headers = Self::parse_headers(&self.headers);
+        let mut request_headers = reqwest::header::HeaderMap::new();
 
         // Add user agent
         request_headers.insert(
@@ -75,6 +207,10 @@ impl Request {
                 .map_err(|e| PepeError::HeaderParseError(e.to_string()))?,
         );
 
+        if let Some(header) = self.settings.auth.as_ref().and_then(Auth::static_header) {
+            request_headers.insert(AUTHORIZATION, header.map_err(PepeError::HeaderParseError)?);
+        }
+
         let mut client_builder = reqwest::Client::builder()
             .default_headers(request_headers)
             .timeout(std::time::Duration::from_secs(self.settings.timeout as u64));
@@ -107,3 +243,117 @@ impl Request {
         Ok(client)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::Vars;
+
+    fn settings(auth: Option<Auth>) -> RequestSettings {
+        RequestSettings {
+            timeout: 20,
+            disable_compression: false,
+            disable_keepalive: false,
+            disable_redirects: false,
+            proxy: None,
+            user_agent: "pepe".to_string(),
+            auth,
+        }
+    }
+
+    fn request(body: &str) -> Request {
+        Request::new(
+            "http://localhost/{{seq}}".to_string(),
+            "POST".to_string(),
+            Some(Bytes::from(body.to_string())),
+            vec![],
+            settings(None),
+        )
+    }
+
+    fn with_headers(headers: &[&str], auth: Option<Auth>) -> Request {
+        Request::new(
+            "http://localhost/".to_string(),
+            "GET".to_string(),
+            None,
+            headers.iter().map(|header| header.to_string()).collect(),
+            settings(auth),
+        )
+    }
+
+    #[test]
+    fn inline_body_is_rendered() {
+        let vars = Vars::new();
+        let rendered = request(r#"{"id": {{seq}}}"#).render(&Context {
+            seq: 7,
+            vars: &vars,
+        });
+        assert_eq!(rendered.url, "http://localhost/7");
+        assert_eq!(rendered.body.as_deref(), Some(br#"{"id": 7}"#.as_slice()));
+    }
+
+    #[test]
+    fn raw_body_is_sent_as_is() {
+        let vars = Vars::new();
+        let rendered = request("{{ a b }}").raw_body().render(&Context {
+            seq: 7,
+            vars: &vars,
+        });
+        assert_eq!(rendered.url, "http://localhost/7");
+        assert_eq!(rendered.body.as_deref(), Some(b"{{ a b }}".as_slice()));
+    }
+
+    #[test]
+    fn requests_sharing_a_client_send_their_own_headers() {
+        let client = reqwest::Client::new();
+        let first = with_headers(&["X-Step: 1", "Accept: text/plain"], None);
+        let second = with_headers(&["X-Step: 2"], None);
+
+        let first = first.build(&client).build().unwrap();
+        let second = second.build(&client).build().unwrap();
+        assert_eq!(first.headers()["x-step"], "1");
+        assert_eq!(first.headers()["accept"], "text/plain");
+        assert_eq!(second.headers()["x-step"], "2");
+        assert!(second.headers().get("accept").is_none());
+    }
+
+    #[test]
+    fn templated_headers_are_rendered_for_every_request() {
+        let client = reqwest::Client::new();
+        let vars = Vars::new();
+        let request = with_headers(&["X-Seq: {{seq}}", "X-Static: a"], None)
+            .render(&Context {
+                seq: 3,
+                vars: &vars,
+            })
+            .build(&client)
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["x-seq"], "3");
+        assert_eq!(request.headers()["x-static"], "a");
+    }
+
+    #[test]
+    fn credentials_win_over_an_authorization_header() {
+        let client = reqwest::Client::new();
+        let request = with_headers(
+            &["Authorization: Basic other"],
+            Some(Auth::Bearer("token".to_string())),
+        )
+        .build(&client)
+        .build()
+        .unwrap();
+        // The Authorization header of static credentials is set on the client
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        assert!(check_header("Accept: application/json").is_ok());
+        assert!(check_header("X-Id: {{seq}}").is_ok());
+        assert!(check_header("Accept").is_err());
+        assert!(check_header("X Id: 1").is_err());
+        assert!(check_header_field("X-Id", "a\nb").is_err());
+        assert!(check_header_field("X:Id", "1").is_err());
+    }
+}
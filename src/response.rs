@@ -1,45 +1,277 @@
+use std::error::Error;
+
 use crate::cache::CacheStatus;
 
+// ErrorKind is the category of a request that failed without a response,
+// or whose response body could not be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ErrorKind {
+    Dns,
+    ConnectionRefused,
+    ConnectionReset,
+    Tls,
+    ConnectTimeout,
+    ReadTimeout,
+    // Other failures to connect, e.g. an unreachable network
+    Connect,
+    TooManyRedirects,
+    Body,
+    Decode,
+    Request,
+    Other,
+}
+
+// Failing requests of a kind of error, with a few of their messages
+#[derive(Debug, Clone, Default)]
+pub struct ErrorCount {
+    pub count: usize,
+    pub samples: Vec<String>,
+}
+
+// Distinct messages kept as samples of a kind of error
+const MAX_ERROR_SAMPLES: usize = 3;
+
+impl ErrorKind {
+    /// Classify an error from its reqwest category, then from its causes,
+    /// e.g. the IO error of a refused connection or the message of a DNS lookup
+    pub fn from_error(error: &reqwest::Error) -> ErrorKind {
+        if error.is_timeout() {
+            return ErrorKind::timeout(error);
+        }
+        if error.is_redirect() {
+            return ErrorKind::TooManyRedirects;
+        }
+        if error.is_decode() {
+            return ErrorKind::Decode;
+        }
+
+        let mut source = error.source();
+        while let Some(cause) = source {
+            if let Some(mut io_error) = cause.downcast_ref::<std::io::Error>() {
+                // IO errors nest, e.g. a TLS error is wrapped in an error of kind Other
+                while let Some(inner) = io_error
+                    .get_ref()
+                    .and_then(|inner| inner.downcast_ref::<std::io::Error>())
+                {
+                    io_error = inner;
+                }
+                match io_error.kind() {
+                    std::io::ErrorKind::ConnectionRefused => return ErrorKind::ConnectionRefused,
+                    std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof => return ErrorKind::ConnectionReset,
+                    std::io::ErrorKind::TimedOut => return ErrorKind::timeout(error),
+                    // The TLS handshake is the only part of a connection that decodes data
+                    std::io::ErrorKind::InvalidData if error.is_connect() => return ErrorKind::Tls,
+                    _ => {}
+                }
+            }
+            // The server closed the connection before the response was complete
+            if cause
+                .downcast_ref::<hyper::Error>()
+                .is_some_and(|e| e.is_incomplete_message() || e.is_canceled())
+            {
+                return ErrorKind::ConnectionReset;
+            }
+
+            // DNS and TLS errors are only told apart by their message
+            let message = cause.to_string().to_lowercase();
+            if message.contains("dns error") || message.contains("failed to lookup address") {
+                return ErrorKind::Dns;
+            }
+            if message.contains("certificate")
+                || message.contains("tls")
+                || message.contains("handshake")
+            {
+                return ErrorKind::Tls;
+            }
+            source = cause.source();
+        }
+
+        if error.is_connect() {
+            ErrorKind::Connect
+        } else if error.is_body() {
+            ErrorKind::Body
+        } else if error.is_request() {
+            ErrorKind::Request
+        } else {
+            ErrorKind::Other
+        }
+    }
+
+    fn timeout(error: &reqwest::Error) -> ErrorKind {
+        if error.is_connect() {
+            ErrorKind::ConnectTimeout
+        } else {
+            ErrorKind::ReadTimeout
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, ErrorKind::ConnectTimeout | ErrorKind::ReadTimeout)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Dns => "dns",
+            ErrorKind::ConnectionRefused => "connection_refused",
+            ErrorKind::ConnectionReset => "connection_reset",
+            ErrorKind::Tls => "tls",
+            ErrorKind::ConnectTimeout => "connect_timeout",
+            ErrorKind::ReadTimeout => "read_timeout",
+            ErrorKind::Connect => "connect",
+            ErrorKind::TooManyRedirects => "too_many_redirects",
+            ErrorKind::Body => "body",
+            ErrorKind::Decode => "decode",
+            ErrorKind::Request => "request",
+            ErrorKind::Other => "other",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ErrorKind::Dns => "DNS failure",
+            ErrorKind::ConnectionRefused => "Connection refused",
+            ErrorKind::ConnectionReset => "Connection reset",
+            ErrorKind::Tls => "TLS error",
+            ErrorKind::ConnectTimeout => "Connect timeout",
+            ErrorKind::ReadTimeout => "Read timeout",
+            ErrorKind::Connect => "Connect error",
+            ErrorKind::TooManyRedirects => "Too many redirects",
+            ErrorKind::Body => "Body error",
+            ErrorKind::Decode => "Decode error",
+            ErrorKind::Request => "Request error",
+            ErrorKind::Other => "Other error",
+        }
+    }
+}
+
+impl ErrorCount {
+    pub fn record(&mut self, message: &str) {
+        self.count += 1;
+        if self.samples.len() < MAX_ERROR_SAMPLES
+            && !self.samples.iter().any(|sample| sample == message)
+        {
+            self.samples.push(message.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResponseError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ResponseError {
+    pub fn from_error(error: &reqwest::Error) -> ResponseError {
+        // The message of a reqwest error is generic, the cause is in its sources
+        let mut message = error.to_string();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+
+        ResponseError {
+            kind: ErrorKind::from_error(error),
+            message,
+        }
+    }
+}
+
+// Response is what the checks and the captures see of a response, before its body is truncated
+pub struct Response<'a> {
+    pub status: reqwest::StatusCode,
+    pub headers: &'a reqwest::header::HeaderMap,
+    pub body: &'a str,
+    pub duration: std::time::Duration,
+}
+
 #[derive(Debug, Clone)]
 pub struct ResponseStats {
+    pub method: String,
+    pub url: String,
+    // Wall clock time the request was sent at
+    pub timestamp: std::time::SystemTime,
     pub duration: std::time::Duration,
     pub status_code: Option<reqwest::StatusCode>,
+    // Whether the status code is one of --expect-status, 2xx by default
+    pub expected_status: bool,
     pub content_length: Option<u64>,
     pub partial_response: Option<String>,
     pub dns_times: Option<(std::time::Duration, std::time::Duration)>,
     pub cache_status: Option<CacheStatus>,
+    // Time between the intended send time and the actual send time, only set with --rate
+    pub queue_delay: Option<std::time::Duration>,
+    // Index of the load profile stage the request was sent in
+    pub stage: Option<usize>,
+    // Index of the --url-file endpoint or of the --scenario step the request was sent to
+    pub endpoint: Option<usize>,
+    // Duration of the scenario iteration, only set on the last step it ran
+    pub iteration: Option<std::time::Duration>,
+    // Whether each --check passed, in the order of the command line
+    pub checks: Vec<bool>,
+    pub error: Option<ResponseError>,
 }
 
 impl Default for ResponseStats {
     fn default() -> Self {
         Self {
+            method: String::new(),
+            url: String::new(),
+            timestamp: std::time::SystemTime::now(),
             duration: std::time::Duration::default(),
             status_code: None,
+            expected_status: false,
             content_length: None,
             partial_response: None,
             dns_times: None,
             cache_status: None,
+            queue_delay: None,
+            stage: None,
+            endpoint: None,
+            iteration: None,
+            checks: Vec::new(),
+            error: None,
         }
     }
 }
 
 impl ResponseStats {
+    /// Build the stats of a response, `inspect` is given the response with its full body
+    /// once the latency is measured, before the body is truncated
     pub async fn from_response(
         resp: Result<reqwest::Response, reqwest::Error>,
         start: std::time::Instant,
         dns_times: (std::time::Duration, std::time::Duration),
+        inspect: impl FnOnce(&Response),
     ) -> Self {
         let response_headers = resp
             .as_ref()
             .map(|r| r.headers().clone())
             .unwrap_or_default();
 
+        let timestamp = std::time::SystemTime::now() - start.elapsed();
         let cache_status = CacheStatus::parse_headers(&response_headers);
         let stats = match resp {
             Ok(resp) => {
                 let status_code = resp.status();
                 let content_length = resp.content_length();
-                let text = resp.text().await.unwrap_or_else(|_| "".to_string());
+                // A body that can't be read fails the request, e.g. a connection reset mid-body
+                let (text, error) = match resp.text().await {
+                    Ok(text) => (text, None),
+                    Err(e) => (String::new(), Some(ResponseError::from_error(&e))),
+                };
+                let duration = start.elapsed();
+                inspect(&Response {
+                    status: status_code,
+                    headers: &response_headers,
+                    body: &text,
+                    duration,
+                });
                 let text = text.trim().replace("\n", " ").replace("\r", " ");
                 let truncated_text = if text.len() > 100 {
                     text.chars().take(100).collect::<String>()
@@ -48,30 +280,123 @@ impl ResponseStats {
                 };
 
                 ResponseStats {
-                    duration: start.elapsed(),
+                    method: String::new(),
+                    url: String::new(),
+                    timestamp,
+                    duration,
                     status_code: Some(status_code),
+                    expected_status: status_code.is_success(),
                     content_length,
                     partial_response: Some(truncated_text),
-                    dns_times: None,
+                    dns_times: Some(dns_times),
                     cache_status,
+                    queue_delay: None,
+                    stage: None,
+                    endpoint: None,
+                    iteration: None,
+                    checks: Vec::new(),
+                    error,
                 }
             }
             Err(e) => {
-                // Capture timeout errors
+                // The request failed without a response, e.g. a timeout or a refused connection
                 let status_code = e.status();
                 let content_length = None;
                 let partial_response = None;
                 ResponseStats {
+                    method: String::new(),
+                    url: String::new(),
+                    timestamp,
                     duration: start.elapsed(),
                     status_code,
+                    expected_status: false,
                     content_length,
                     partial_response,
                     dns_times: Some(dns_times),
                     cache_status,
+                    queue_delay: None,
+                    stage: None,
+                    endpoint: None,
+                    iteration: None,
+                    checks: Vec::new(),
+                    error: Some(ResponseError::from_error(&e)),
                 }
             }
         };
 
         stats
     }
+
+    /// Whether the request got a full response with an expected status that passed every check
+    pub fn is_success(&self) -> bool {
+        self.status_code.is_some()
+            && self.error.is_none()
+            && self.expected_status
+            && self.checks.iter().all(|passed| *passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    async fn error_of(url: &str) -> ResponseError {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let error = client.get(url).send().await.unwrap_err();
+        ResponseError::from_error(&error)
+    }
+
+    #[tokio::test]
+    async fn refused_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let error = error_of(&format!("http://{}/", address)).await;
+        assert_eq!(error.kind, ErrorKind::ConnectionRefused);
+        assert!(!error.kind.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn connection_closed_before_the_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 1024]).await;
+        });
+
+        let error = error_of(&format!("http://{}/", address)).await;
+        assert_eq!(error.kind, ErrorKind::ConnectionReset);
+    }
+
+    #[tokio::test]
+    async fn server_that_never_answers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let error = error_of(&format!("http://{}/", address)).await;
+        assert_eq!(error.kind, ErrorKind::ReadTimeout);
+        assert!(error.kind.is_timeout());
+        assert!(error.message.contains("timed out"), "{}", error.message);
+    }
+
+    #[test]
+    fn error_samples_are_distinct_and_bounded() {
+        let mut count = ErrorCount::default();
+        for message in ["a", "a", "b", "c", "d"] {
+            count.record(message);
+        }
+        assert_eq!(count.count, 5);
+        assert_eq!(count.samples, ["a", "b", "c"]);
+    }
 }
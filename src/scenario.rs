@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bytes::Bytes;
+use serde::Deserialize;
+
+use crate::endpoint::{read_body, resolve_url};
+use crate::extract::{Capture, Source};
+use crate::request::{check_header_field, Request};
+
+// Scenario file, the steps run in order by every iteration of a virtual user
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    steps: Vec<StepConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepConfig {
+    name: Option<String>,
+    method: Option<String>,
+    url: String,
+    headers: Option<BTreeMap<String, String>>,
+    body: Option<String>,
+    body_file: Option<String>,
+    // JSON Schema file the response bodies are validated against
+    schema: Option<String>,
+    // Values of the response stored in variables for the next steps
+    extract: Option<BTreeMap<String, Source>>,
+}
+
+// Step is a request of a scenario, its URL, headers and inline body are templates
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    // Headers of the step, sent along with the headers of the command line
+    pub headers: Vec<String>,
+    pub body: Option<Bytes>,
+    // Bodies of body_file are sent as is, inline bodies are templates
+    pub raw_body: bool,
+    pub schema: Option<String>,
+    pub captures: Vec<Capture>,
+}
+
+// StepRequest is a step ready to be sent by an iteration
+#[derive(Debug, Clone)]
+pub struct StepRequest {
+    pub request: Request,
+    pub captures: Vec<Capture>,
+}
+
+/// Load the steps of a scenario file
+/// Relative URLs are appended to the base URL, body and schema files are relative to the scenario file
+pub fn load(path: &str, base_url: &str) -> Result<Vec<Step>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read scenario file {}: {}", path, e))?;
+    let scenario: ScenarioFile =
+        toml::from_str(&content).map_err(|e| format!("Invalid scenario file {}: {}", path, e))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    if scenario.steps.is_empty() {
+        return Err(format!("Scenario file {} has no steps", path));
+    }
+
+    scenario
+        .steps
+        .into_iter()
+        .enumerate()
+        .map(|(i, step)| {
+            Step::new(step, base_url, dir)
+                .map_err(|e| format!("Invalid step {} in {}: {}", i + 1, path, e))
+        })
+        .collect()
+}
+
+impl Step {
+    fn new(config: StepConfig, base_url: &str, dir: &Path) -> Result<Self, String> {
+        let method = config
+            .method
+            .map(|method| method.to_uppercase())
+            .unwrap_or_else(|| "GET".to_string());
+        reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|_| format!("Invalid method: {}", method))?;
+
+        let raw_body = config.body_file.is_some();
+        let body = match (config.body, config.body_file) {
+            (Some(_), Some(_)) => return Err("body and body_file cannot both be set".to_string()),
+            (Some(body), None) => Some(Bytes::from(body)),
+            (None, Some(body_file)) => Some(read_body(dir, &body_file)?),
+            (None, None) => None,
+        };
+
+        let captures = config
+            .extract
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, source)| Capture::new(&name, source))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let headers = config
+            .headers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| {
+                check_header_field(&name, &value)?;
+                Ok(format!("{}: {}", name, value))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            name: config
+                .name
+                .unwrap_or_else(|| format!("{} {}", method, config.url)),
+            url: resolve_url(base_url, &config.url)?,
+            method,
+            headers,
+            body,
+            raw_body,
+            schema: config
+                .schema
+                .map(|schema| dir.join(schema).to_string_lossy().into_owned()),
+            captures,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::extract_all;
+    use crate::request::RequestSettings;
+    use crate::response::Response;
+    use crate::template::{Context, Vars};
+
+    /// Write a scenario file of a test, its name is unique to the test
+    fn scenario(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("pepe-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn request(step: &Step) -> Request {
+        Request::new(
+            step.url.clone(),
+            step.method.clone(),
+            step.body.clone(),
+            step.headers.clone(),
+            RequestSettings {
+                timeout: 20,
+                disable_compression: false,
+                disable_keepalive: false,
+                disable_redirects: false,
+                proxy: None,
+                user_agent: "pepe".to_string(),
+                auth: None,
+            },
+        )
+    }
+
+    #[test]
+    fn steps_are_loaded_in_order() {
+        let path = scenario(
+            "order.toml",
+            r#"
+            [[steps]]
+            name = "login"
+            method = "post"
+            url = "/login"
+
+            [[steps]]
+            url = "https://other.example.com/items"
+            headers = { Accept = "application/json" }
+            "#,
+        );
+        let steps = load(&path, "http://localhost:8080/").unwrap();
+
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].name, "login");
+        assert_eq!(steps[0].method, "POST");
+        assert_eq!(steps[0].url, "http://localhost:8080/login");
+        // Unnamed steps are named after their request, absolute URLs are kept
+        assert_eq!(steps[1].name, "GET https://other.example.com/items");
+        assert_eq!(steps[1].url, "https://other.example.com/items");
+        assert_eq!(steps[1].headers, ["Accept: application/json"]);
+    }
+
+    #[test]
+    fn invalid_steps_are_rejected() {
+        let invalid = [
+            ("missing-url.toml", "[[steps]]\nname = \"login\"\n", "url"),
+            (
+                "unknown-extract.toml",
+                "[[steps]]\nurl = \"/\"\nextract = { id = { xpath = \"//id\" } }\n",
+                "xpath",
+            ),
+            (
+                "header-name.toml",
+                "[[steps]]\nurl = \"/\"\nheaders = { \"X Id\" = \"1\" }\n",
+                "Invalid header name: X Id",
+            ),
+            ("no-steps.toml", "steps = []\n", "has no steps"),
+        ];
+        for (name, content, message) in invalid {
+            let error = load(&scenario(name, content), "http://localhost").unwrap_err();
+            assert!(error.contains(message), "{}: {}", name, error);
+        }
+    }
+
+    #[test]
+    fn captures_are_used_by_the_next_steps() {
+        let path = scenario(
+            "captures.toml",
+            r#"
+            [[steps]]
+            url = "/login"
+            extract = { token = { json = "$.token" } }
+
+            [[steps]]
+            method = "POST"
+            url = "/items/{{token}}"
+            headers = { Authorization = "Bearer {{token}}" }
+            body = '{"token": "{{token}}"}'
+            "#,
+        );
+        let steps = load(&path, "http://localhost").unwrap();
+
+        let mut vars = Vars::new();
+        vars.extend(extract_all(
+            &steps[0].captures,
+            &Response {
+                status: reqwest::StatusCode::OK,
+                headers: &reqwest::header::HeaderMap::new(),
+                body: r#"{"token": "abc"}"#,
+                duration: std::time::Duration::ZERO,
+            },
+        ));
+
+        let request = request(&steps[1])
+            .render(&Context {
+                seq: 0,
+                vars: &vars,
+            })
+            .build(&reqwest::Client::new())
+            .build()
+            .unwrap();
+        assert_eq!(request.url().as_str(), "http://localhost/items/abc");
+        assert_eq!(request.headers()["authorization"], "Bearer abc");
+        assert_eq!(
+            request.body().and_then(|body| body.as_bytes()),
+            Some(br#"{"token": "abc"}"#.as_slice())
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+// Bodies waiting to be validated, samples beyond it are skipped to bound memory
+const MAX_PENDING: usize = 1024;
+
+// Failing paths kept as samples of the violations
+const MAX_SAMPLES: usize = 5;
+
+// Violation is a failing path of a body, e.g. /items/0/id: "7" is not of type "integer"
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    pub path: String,
+    pub message: String,
+}
+
+// Outcome of the validations of a schema over a test
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SchemaStats {
+    pub validated: usize,
+    pub violations: usize,
+    // Samples dropped while the validation was behind
+    pub skipped: usize,
+    pub samples: Vec<Violation>,
+}
+
+impl SchemaStats {
+    /// Validated bodies that violate the schema, in percent
+    pub fn violation_rate(&self) -> f64 {
+        if self.validated == 0 {
+            return 0.0;
+        }
+        self.violations as f64 * 100.0 / self.validated as f64
+    }
+}
+
+// Schema validates a fraction of the response bodies of an endpoint against a JSON Schema
+// Bodies are validated on the blocking pool, away from the requests and their latency
+#[derive(Debug)]
+pub struct Schema {
+    pub path: String,
+    validator: jsonschema::Validator,
+    // Fraction of the successful responses validated, from 0 to 1
+    sample: f64,
+    pending: AtomicUsize,
+    stats: Mutex<SchemaStats>,
+}
+
+/// Parse the fraction of responses to validate, e.g. 0.1 or 10%
+pub fn parse_sample(input: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid schema sample: {}, expected e.g. 0.1 or 10%", input);
+    let sample = match input.trim().strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().map_err(|_| invalid())? / 100.0,
+        None => input.trim().parse::<f64>().map_err(|_| invalid())?,
+    };
+    if !(0.0..=1.0).contains(&sample) {
+        return Err(invalid());
+    }
+    Ok(sample)
+}
+
+impl Schema {
+    pub fn load(path: &str, sample: f64) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read schema file {}: {}", path, e))?;
+        let schema: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid schema file {}: {}", path, e))?;
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| format!("Invalid schema file {}: {}", path, e))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            validator,
+            sample,
+            pending: AtomicUsize::new(0),
+            stats: Mutex::new(SchemaStats::default()),
+        })
+    }
+
+    pub fn stats(&self) -> SchemaStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Clear the outcome of a previous run of the test
+    pub fn reset(&self) {
+        *self.stats.lock().unwrap() = SchemaStats::default();
+    }
+
+    /// Validate a response body if it is part of the sample
+    pub fn submit(self: &Arc<Self>, body: &str) {
+        if self.sample < 1.0 && fastrand::f64() >= self.sample {
+            return;
+        }
+        if self.pending.fetch_add(1, Ordering::AcqRel) >= MAX_PENDING {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            self.stats.lock().unwrap().skipped += 1;
+            return;
+        }
+
+        let schema = self.clone();
+        let body = body.to_string();
+        tokio::task::spawn_blocking(move || {
+            let violations = schema.validate(&body);
+            schema.record(violations);
+            schema.pending.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+
+    /// Wait for the bodies submitted so far to be validated
+    pub async fn settle(&self) {
+        while self.pending.load(Ordering::Acquire) > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    fn validate(&self, body: &str) -> Vec<Violation> {
+        let instance: serde_json::Value = match serde_json::from_str(body) {
+            Ok(instance) => instance,
+            Err(e) => {
+                return vec![Violation {
+                    path: "/".to_string(),
+                    message: format!("body is not JSON: {}", e),
+                }]
+            }
+        };
+
+        self.validator
+            .iter_errors(&instance)
+            .map(|error| {
+                let path = error.instance_path.to_string();
+                Violation {
+                    path: if path.is_empty() {
+                        "/".to_string()
+                    } else {
+                        path
+                    },
+                    message: error.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    fn record(&self, violations: Vec<Violation>) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.validated += 1;
+        if violations.is_empty() {
+            return;
+        }
+
+        stats.violations += 1;
+        // One sample per path, the first violations found are kept
+        for violation in violations {
+            if stats.samples.len() >= MAX_SAMPLES {
+                break;
+            }
+            if !stats
+                .samples
+                .iter()
+                .any(|sample| sample.path == violation.path)
+            {
+                stats.samples.push(violation);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(sample: f64) -> Arc<Schema> {
+        let path = std::env::temp_dir().join(format!("pepe-{}-item.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{
+                "type": "object",
+                "required": ["id"],
+                "properties": {
+                    "id": { "type": "integer" },
+                    "tags": { "type": "array", "items": { "type": "string" } }
+                }
+            }"#,
+        )
+        .unwrap();
+        Arc::new(Schema::load(&path.to_string_lossy(), sample).unwrap())
+    }
+
+    #[test]
+    fn samples_are_fractions_or_percents() {
+        assert_eq!(parse_sample("0.1"), Ok(0.1));
+        assert_eq!(parse_sample("10%"), Ok(0.1));
+        assert_eq!(parse_sample(" 100 % "), Ok(1.0));
+        assert_eq!(parse_sample("0"), Ok(0.0));
+        assert!(parse_sample("1.5").is_err());
+        assert!(parse_sample("150%").is_err());
+        assert!(parse_sample("-0.1").is_err());
+        assert!(parse_sample("ten").is_err());
+    }
+
+    #[tokio::test]
+    async fn only_the_sample_of_the_bodies_is_validated() {
+        let all = schema(1.0);
+        let none = schema(0.0);
+        for _ in 0..10 {
+            all.submit(r#"{"id": 1}"#);
+            none.submit(r#"{"id": 1}"#);
+        }
+        all.settle().await;
+        none.settle().await;
+
+        assert_eq!(all.stats().validated, 10);
+        assert_eq!(none.stats().validated, 0);
+    }
+
+    #[test]
+    fn violations_are_reported_at_their_instance_path() {
+        let schema = schema(1.0);
+        assert!(schema.validate(r#"{"id": 1, "tags": ["a"]}"#).is_empty());
+
+        let paths = |body: &str| -> Vec<String> {
+            schema
+                .validate(body)
+                .into_iter()
+                .map(|violation| violation.path)
+                .collect()
+        };
+        assert_eq!(paths(r#"{"id": "7"}"#), ["/id"]);
+        assert_eq!(paths(r#"{"id": 1, "tags": ["a", 2]}"#), ["/tags/1"]);
+        // Violations of the whole body, and bodies that are not JSON, are at the root
+        assert_eq!(paths(r#"{"tags": []}"#), ["/"]);
+        assert_eq!(paths("<html>"), ["/"]);
+    }
+}
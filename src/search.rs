@@ -0,0 +1,133 @@
+use crate::cli::Cli;
+use crate::headless;
+use crate::sink::Sinks;
+use crate::stats::Stats;
+use crate::threshold::Threshold;
+use crate::utils::format_duration;
+use crate::PepeError;
+
+// Duration of each step when -z is not set
+const DEFAULT_STEP_DURATION: std::time::Duration = std::time::Duration::from_secs(10);
+
+// A step is sustained when at least this fraction of the target rate was sent
+const MIN_SUSTAINED_RATIO: f64 = 0.95;
+
+// Result of one step of a capacity search
+struct Step {
+    target: f64,
+    stats: Stats,
+    breached: Vec<String>,
+}
+
+impl Step {
+    fn passed(&self) -> bool {
+        self.breached.is_empty()
+    }
+}
+
+/// Run a test at a fixed rate and collect its statistics
+async fn measure(args: &Cli, duration: std::time::Duration) -> Result<Stats, PepeError> {
+    let mut stats = headless::collect(args, |_| {}).await?.stats;
+
+    // Rates are measured over the time the load was offered, without the drain
+    stats.duration = duration;
+    Ok(stats)
+}
+
+/// Raise the offered load step by step until the SLO is breached
+/// and print the highest sustainable rate
+pub async fn run(args: &Cli) -> Result<(), PepeError> {
+    let slo: Vec<Threshold> = args.slo_thresholds();
+    let step_duration = args.run_duration().unwrap_or(DEFAULT_STEP_DURATION);
+    let expressions: Vec<&str> = slo.iter().map(|t| t.expression.as_str()).collect();
+
+    eprintln!(
+        "Capacity search: {} for {} per step, starting at {} req/s",
+        expressions.join(", "),
+        format_duration(step_duration),
+        args.search_start
+    );
+
+    // Every step writes to the same --csv and --log-requests files
+    let mut args = args.clone();
+    args.sinks = Sinks::open_shared(&args)?;
+    let args = &args;
+
+    let mut steps: Vec<Step> = Vec::new();
+    let mut target = args.search_start;
+    while args.search_max.is_none_or(|max| target <= max) {
+        eprintln!("Step {}: {} req/s", steps.len() + 1, target);
+
+        let mut step_args = args.clone();
+        step_args.rate = Some(target);
+        step_args.duration = Some(format!("{}ms", step_duration.as_millis()));
+        step_args.stages = None;
+
+        let stats = measure(&step_args, step_duration).await?;
+
+        let mut breached: Vec<String> = slo
+            .iter()
+            .filter(|threshold| !threshold.holds(&stats))
+            .map(|threshold| format!("{} ({})", threshold.expression, threshold.observed(&stats)))
+            .collect();
+        if stats.send_rate() < target * MIN_SUSTAINED_RATIO {
+            breached.push(format!(
+                "rate not sustained ({:.1} req/s)",
+                stats.send_rate()
+            ));
+        }
+
+        steps.push(Step {
+            target,
+            stats,
+            breached,
+        });
+
+        if !steps.last().is_some_and(Step::passed) {
+            break;
+        }
+        target += args.search_step;
+    }
+
+    print_steps(&steps);
+
+    match steps.iter().take_while(|step| step.passed()).last() {
+        Some(step) if steps.iter().all(Step::passed) => {
+            println!("\nSLO held up to the maximum rate: {} req/s", step.target)
+        }
+        Some(step) => println!("\nHighest sustainable rate: {} req/s", step.target),
+        None => println!("\nNo step met the SLO, try a lower --search-start"),
+    }
+
+    Ok(())
+}
+
+fn print_steps(steps: &[Step]) {
+    println!(
+        "\n{:>4}  {:>10}  {:>10}  {:>8}  {:>10}  {:>8}  {:>8}  {:>13}  Result",
+        "Step", "Target", "Actual", "Requests", "Error rate", "P50", "P99", "Corrected P99"
+    );
+
+    for (i, step) in steps.iter().enumerate() {
+        let latencies = &step.stats.latencies;
+        let corrected = &step.stats.corrected_latencies;
+        let result = if step.passed() {
+            "pass".to_string()
+        } else {
+            format!("fail: {}", step.breached.join(", "))
+        };
+
+        println!(
+            "{:>4}  {:>10.1}  {:>10.1}  {:>8}  {:>9.2}%  {:>6}ms  {:>6}ms  {:>11}ms  {}",
+            i + 1,
+            step.target,
+            step.stats.send_rate(),
+            step.stats.count,
+            step.stats.error_rate(),
+            latencies.percentile(50.0),
+            latencies.percentile(99.0),
+            corrected.percentile(99.0),
+            result
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::cli::Cli;
+use crate::response::ResponseStats;
+use crate::{PepeError, RESULT_CHANNEL_CAPACITY};
+
+// Rows are flushed at this interval, the files can be followed while a test runs
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+const CSV_HEADER: [&str; 10] = [
+    "timestamp_ms",
+    "latency_ms",
+    "status",
+    "content_length",
+    "cache_status",
+    "dns_lookup_ms",
+    "dns_resolution_ms",
+    "error_kind",
+    "error",
+    "endpoint",
+];
+
+// RequestEvent is a line of the request log
+#[derive(Serialize)]
+struct RequestEvent<'a> {
+    method: &'a str,
+    url: &'a str,
+    // Label of the --url-file endpoint, e.g. GET /search
+    endpoint: Option<&'a str>,
+    // Time between the start of the test and the request, in milliseconds
+    start_offset_ms: f64,
+    duration_ms: f64,
+    status: Option<u16>,
+    bytes: Option<u64>,
+    cache_status: Option<String>,
+    error: Option<&'a str>,
+    partial_response: Option<&'a str>,
+}
+
+// Sinks write every response as it is produced, before it is aggregated
+#[derive(Debug)]
+pub struct Sinks {
+    start: std::time::SystemTime,
+    endpoints: Vec<String>,
+    csv: Option<csv::Writer<File>>,
+    log: Option<BufWriter<File>>,
+}
+
+impl Sinks {
+    fn open(args: &Cli) -> Result<Self, PepeError> {
+        let csv = match &args.csv {
+            Some(path) => {
+                let mut writer =
+                    csv::Writer::from_path(path).map_err(|e| PepeError::IoError(e.into()))?;
+                writer
+                    .write_record(CSV_HEADER)
+                    .map_err(|e| PepeError::IoError(e.into()))?;
+                Some(writer)
+            }
+            None => None,
+        };
+
+        let log = match &args.log_requests {
+            Some(path) => Some(BufWriter::new(
+                File::create(path).map_err(PepeError::IoError)?,
+            )),
+            None => None,
+        };
+
+        Ok(Self {
+            start: std::time::SystemTime::now(),
+            endpoints: args.endpoint_labels(),
+            csv,
+            log,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.csv.is_none() && self.log.is_none()
+    }
+
+    /// Open the sinks once for several tests, e.g. the steps of a capacity search,
+    /// None when no sink is enabled
+    pub fn open_shared(args: &Cli) -> Result<Option<Arc<Mutex<Self>>>, PepeError> {
+        let sinks = Self::open(args)?;
+        Ok((!sinks.is_empty()).then(|| Arc::new(Mutex::new(sinks))))
+    }
+
+    fn write(&mut self, stat: &ResponseStats) {
+        let endpoint = stat
+            .endpoint
+            .and_then(|i| self.endpoints.get(i))
+            .map(String::as_str);
+
+        if let Some(writer) = &mut self.csv {
+            // A failed write drops the sample, the test goes on
+            let _ = writer.write_record(csv_row(stat, endpoint));
+        }
+
+        if let Some(writer) = &mut self.log {
+            let event = RequestEvent {
+                method: &stat.method,
+                url: &stat.url,
+                endpoint,
+                start_offset_ms: stat
+                    .timestamp
+                    .duration_since(self.start)
+                    .unwrap_or_default()
+                    .as_secs_f64()
+                    * 1000.0,
+                duration_ms: stat.duration.as_secs_f64() * 1000.0,
+                status: stat.status_code.map(|status_code| status_code.as_u16()),
+                bytes: stat.content_length,
+                cache_status: stat
+                    .cache_status
+                    .as_ref()
+                    .map(|cache_status| format!("{:?}", cache_status)),
+                error: stat.error.as_ref().map(|error| error.message.as_str()),
+                partial_response: stat.partial_response.as_deref(),
+            };
+            if serde_json::to_writer(&mut *writer, &event).is_ok() {
+                let _ = writer.write_all(b"\n");
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(writer) = &mut self.csv {
+            let _ = writer.flush();
+        }
+        if let Some(writer) = &mut self.log {
+            let _ = writer.flush();
+        }
+    }
+}
+
+fn csv_row(stat: &ResponseStats, endpoint: Option<&str>) -> [String; 10] {
+    let timestamp = stat
+        .timestamp
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let (dns_lookup, dns_resolution) = match stat.dns_times {
+        Some((lookup, resolution)) => (
+            format!("{:.3}", lookup.as_secs_f64() * 1000.0),
+            format!("{:.3}", resolution.as_secs_f64() * 1000.0),
+        ),
+        None => (String::new(), String::new()),
+    };
+
+    [
+        timestamp.as_millis().to_string(),
+        format!("{:.3}", stat.duration.as_secs_f64() * 1000.0),
+        stat.status_code
+            .map(|status_code| status_code.as_u16().to_string())
+            .unwrap_or_default(),
+        stat.content_length
+            .map(|content_length| content_length.to_string())
+            .unwrap_or_default(),
+        stat.cache_status
+            .as_ref()
+            .map(|cache_status| format!("{:?}", cache_status))
+            .unwrap_or_default(),
+        dns_lookup,
+        dns_resolution,
+        stat.error
+            .as_ref()
+            .map(|error| error.kind.as_str().to_string())
+            .unwrap_or_default(),
+        stat.error
+            .as_ref()
+            .map(|error| error.message.clone())
+            .unwrap_or_default(),
+        endpoint.unwrap_or_default().to_string(),
+    ]
+}
+
+/// Put the sinks enabled by the arguments in front of a result channel
+/// Responses are written, then forwarded unchanged to `tx`. The files are created
+/// for the test, unless the arguments hold sinks shared with other tests
+pub fn tap(
+    args: &Cli,
+    tx: mpsc::Sender<ResponseStats>,
+) -> Result<mpsc::Sender<ResponseStats>, PepeError> {
+    // Shared sinks keep writing to the files of the previous tests
+    let sinks = match &args.sinks {
+        Some(sinks) => sinks.clone(),
+        None => match Sinks::open_shared(args)? {
+            Some(sinks) => sinks,
+            None => return Ok(tx),
+        },
+    };
+
+    let (sink_tx, mut sink_rx) = mpsc::channel::<ResponseStats>(RESULT_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        let mut ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + FLUSH_INTERVAL, FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                stat = sink_rx.recv() => match stat {
+                    Some(stat) => {
+                        sinks.lock().unwrap().write(&stat);
+                        if tx.send(stat).await.is_err() {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => sinks.lock().unwrap().flush(),
+            }
+        }
+        sinks.lock().unwrap().flush();
+    });
+
+    Ok(sink_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheStatus;
+    use crate::response::{ErrorKind, ResponseError};
+    use clap::{CommandFactory, FromArgMatches};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn stat() -> ResponseStats {
+        ResponseStats {
+            method: "GET".to_string(),
+            url: "http://localhost/search?q=1".to_string(),
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_500),
+            duration: Duration::from_micros(12_500),
+            status_code: Some(reqwest::StatusCode::OK),
+            expected_status: true,
+            content_length: Some(42),
+            dns_times: Some((Duration::from_micros(1_250), Duration::from_micros(250))),
+            cache_status: Some(CacheStatus::Hit),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_are_logged_while_the_test_runs() {
+        let path = std::env::temp_dir().join(format!("pepe-{}-requests.jsonl", std::process::id()));
+        let matches = Cli::command()
+            .try_get_matches_from([
+                "pepe",
+                "--log-requests",
+                &path.to_string_lossy(),
+                "http://localhost",
+            ])
+            .unwrap();
+        let args = Cli::from_arg_matches(&matches).unwrap();
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let sink_tx = tap(&args, tx).unwrap();
+        sink_tx.send(stat()).await.unwrap();
+        rx.recv().await.unwrap();
+
+        // The line is flushed before the channel closes
+        tokio::time::sleep(FLUSH_INTERVAL + Duration::from_millis(500)).await;
+        let log = std::fs::read_to_string(&path).unwrap();
+        let event: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        let fields: Vec<&str> = event
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        // serde_json sorts the fields by name
+        assert_eq!(
+            fields,
+            [
+                "bytes",
+                "cache_status",
+                "duration_ms",
+                "endpoint",
+                "error",
+                "method",
+                "partial_response",
+                "start_offset_ms",
+                "status",
+                "url",
+            ]
+        );
+        assert_eq!(event["method"], "GET");
+        assert_eq!(event["url"], "http://localhost/search?q=1");
+        assert_eq!(event["duration_ms"], 12.5);
+        assert_eq!(event["status"], 200);
+        assert_eq!(event["bytes"], 42);
+        assert_eq!(event["cache_status"], "Hit");
+        assert!(event["error"].is_null());
+        drop(sink_tx);
+    }
+
+    #[test]
+    fn csv_columns_keep_their_order() {
+        assert_eq!(
+            CSV_HEADER,
+            [
+                "timestamp_ms",
+                "latency_ms",
+                "status",
+                "content_length",
+                "cache_status",
+                "dns_lookup_ms",
+                "dns_resolution_ms",
+                "error_kind",
+                "error",
+                "endpoint",
+            ]
+        );
+        assert_eq!(
+            csv_row(&stat(), Some("GET /search")),
+            [
+                "1500",
+                "12.500",
+                "200",
+                "42",
+                "Hit",
+                "1.250",
+                "0.250",
+                "",
+                "",
+                "GET /search",
+            ]
+        );
+
+        // Failed requests have an error and no status
+        let failed = ResponseStats {
+            status_code: None,
+            content_length: None,
+            dns_times: None,
+            cache_status: None,
+            error: Some(ResponseError {
+                kind: ErrorKind::ConnectionRefused,
+                message: "connection refused".to_string(),
+            }),
+            ..stat()
+        };
+        assert_eq!(
+            csv_row(&failed, None),
+            [
+                "1500",
+                "12.500",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "connection_refused",
+                "connection refused",
+                "",
+            ]
+        );
+    }
+}
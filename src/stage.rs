@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use crate::utils::{format_duration, parse_duration};
+
+// StageUnit is what the targets of a load profile control
+// Users ramp the number of concurrent requests, Rate ramps the requests per second
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageUnit {
+    Users,
+    Rate,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stage {
+    pub duration: Duration,
+    pub target: f64,
+}
+
+// Stages is a load profile, the target moves linearly from the target of the
+// previous stage (0 for the first one) to the target of the current stage
+#[derive(Debug, Clone)]
+pub struct Stages {
+    pub stages: Vec<Stage>,
+    pub unit: StageUnit,
+}
+
+impl Stages {
+    /// Parse a load profile from a comma separated list of duration:target
+    /// Targets are concurrent users, or requests per second with a /s suffix
+    /// e.g. 30s:200,5m:200,30s:0 or 1m:100/s,5m:500/s
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut stages = Vec::new();
+        let mut unit = None;
+
+        for stage in input.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (duration, target) = stage
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid stage '{}', expected duration:target", stage))?;
+
+            let (target, stage_unit) = match target.trim().strip_suffix("/s") {
+                Some(target) => (target, StageUnit::Rate),
+                None => (target.trim(), StageUnit::Users),
+            };
+            if unit.is_some_and(|unit| unit != stage_unit) {
+                return Err("Stages cannot mix users and requests per second".to_string());
+            }
+            unit = Some(stage_unit);
+
+            let duration = parse_duration(duration)?;
+            if duration.is_zero() {
+                return Err(format!(
+                    "Stage duration must be greater than zero: {}",
+                    stage
+                ));
+            }
+            let target: f64 = target
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid stage target '{}'", target))?;
+            if !target.is_finite() || target < 0.0 {
+                return Err(format!("Stage target must be a positive number: {}", stage));
+            }
+
+            stages.push(Stage { duration, target });
+        }
+
+        match unit {
+            Some(unit) => Ok(Self { stages, unit }),
+            None => Err("At least one stage is required".to_string()),
+        }
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.stages.iter().map(|stage| stage.duration).sum()
+    }
+
+    pub fn max_target(&self) -> f64 {
+        self.stages
+            .iter()
+            .map(|stage| stage.target)
+            .fold(0.0, f64::max)
+    }
+
+    /// Index of the stage running at the given elapsed time
+    /// The last stage is returned once the profile is over
+    pub fn index_at(&self, elapsed: Duration) -> usize {
+        let mut end = Duration::ZERO;
+        for (i, stage) in self.stages.iter().enumerate() {
+            end += stage.duration;
+            if elapsed < end {
+                return i;
+            }
+        }
+        self.stages.len() - 1
+    }
+
+    /// Target at the given elapsed time, interpolated within the stage
+    pub fn target_at(&self, elapsed: Duration) -> f64 {
+        let mut start = Duration::ZERO;
+        let mut from = 0.0;
+        for stage in &self.stages {
+            if elapsed < start + stage.duration {
+                let progress = (elapsed - start).as_secs_f64() / stage.duration.as_secs_f64();
+                return from + (stage.target - from) * progress;
+            }
+            start += stage.duration;
+            from = stage.target;
+        }
+        from
+    }
+
+    /// Number of requests a rate profile has sent by the given elapsed time
+    fn requests_until(&self, elapsed: f64) -> f64 {
+        let mut start = 0.0;
+        let mut from = 0.0;
+        let mut total = 0.0;
+        for stage in &self.stages {
+            let duration = stage.duration.as_secs_f64();
+            let x = (elapsed - start).clamp(0.0, duration);
+            total += from * x + (stage.target - from) * x * x / (2.0 * duration);
+            if elapsed <= start + duration {
+                break;
+            }
+            start += duration;
+            from = stage.target;
+        }
+        total
+    }
+
+    /// Intended send time of the nth request of a rate profile
+    /// None when the profile ends before the request is due
+    pub fn send_time(&self, n: u64, after: Duration) -> Option<Duration> {
+        let total = self.total_duration().as_secs_f64();
+        let due = (n + 1) as f64;
+        if self.requests_until(total) < due {
+            return None;
+        }
+
+        let (mut low, mut high) = (after.as_secs_f64(), total);
+        for _ in 0..64 {
+            let mid = (low + high) / 2.0;
+            if self.requests_until(mid) < due {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Some(Duration::from_secs_f64(high))
+    }
+
+    /// Format a target with its unit, e.g. 200 VUs or 150 req/s
+    pub fn format_target(&self, target: f64) -> String {
+        match self.unit {
+            StageUnit::Users => format!("{:.0} VUs", target),
+            StageUnit::Rate => format!("{:.0} req/s", target),
+        }
+    }
+
+    /// Format a stage, e.g. 30s → 200 VUs
+    pub fn format_stage(&self, index: usize) -> String {
+        let stage = &self.stages[index];
+        format!(
+            "{} → {}",
+            format_duration(stage.duration),
+            self.format_target(stage.target)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_users_stages() {
+        let stages = Stages::parse("30s:200, 5m:200,30s:0").unwrap();
+        assert_eq!(stages.unit, StageUnit::Users);
+        assert_eq!(stages.stages.len(), 3);
+        assert_eq!(stages.stages[1].duration, Duration::from_secs(300));
+        assert_eq!(stages.max_target(), 200.0);
+        assert_eq!(stages.total_duration(), Duration::from_secs(360));
+    }
+
+    #[test]
+    fn parse_rate_stages() {
+        let stages = Stages::parse("1m:100/s,5m:500/s").unwrap();
+        assert_eq!(stages.unit, StageUnit::Rate);
+        assert_eq!(stages.stages[1].target, 500.0);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_stages() {
+        assert!(Stages::parse("").is_err());
+        assert!(Stages::parse("30s").is_err());
+        assert!(Stages::parse("30s:10,1m:10/s").is_err());
+        assert!(Stages::parse("0s:10").is_err());
+        assert!(Stages::parse("30s:-1").is_err());
+        assert!(Stages::parse("30s:ten").is_err());
+        assert!(Stages::parse("1e30s:10").is_err());
+    }
+
+    #[test]
+    fn target_is_interpolated_within_a_stage() {
+        let stages = Stages::parse("10s:100,10s:100,10s:0").unwrap();
+        assert_eq!(stages.target_at(Duration::ZERO), 0.0);
+        assert_eq!(stages.target_at(Duration::from_secs(5)), 50.0);
+        assert_eq!(stages.target_at(Duration::from_secs(15)), 100.0);
+        assert_eq!(stages.target_at(Duration::from_secs(25)), 50.0);
+        assert_eq!(stages.target_at(Duration::from_secs(60)), 0.0);
+    }
+
+    #[test]
+    fn index_of_the_running_stage() {
+        let stages = Stages::parse("10s:100,10s:100").unwrap();
+        assert_eq!(stages.index_at(Duration::from_secs(9)), 0);
+        assert_eq!(stages.index_at(Duration::from_secs(10)), 1);
+        assert_eq!(stages.index_at(Duration::from_secs(60)), 1);
+    }
+
+    #[test]
+    fn send_times_follow_the_rate() {
+        let stages = Stages::parse("10s:10/s").unwrap();
+        // Ramp from 0 to 10 req/s, requests come closer and closer
+        let first = stages.send_time(0, Duration::ZERO).unwrap();
+        let second = stages.send_time(1, first).unwrap();
+        assert!(first < second);
+
+        let flat = Stages::parse("1s:10/s,10s:10/s").unwrap();
+        // 5 requests in the ramp, then one every 100ms
+        let sixth = flat.send_time(5, Duration::ZERO).unwrap();
+        let seventh = flat.send_time(6, sixth).unwrap();
+        assert!(((seventh - sixth).as_secs_f64() - 0.1).abs() < 1e-6);
+        // The profile sends 5 + 100 requests in total
+        assert!(flat.send_time(104, Duration::ZERO).is_some());
+        assert!(flat.send_time(105, Duration::ZERO).is_none());
+    }
+}
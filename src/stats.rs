@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+
+use hdrhistogram::Histogram;
+use reqwest::StatusCode;
+
+use crate::cache::CacheCategory;
+use crate::check::CheckCount;
+use crate::cli::Cli;
+use crate::response::{ErrorCount, ErrorKind, ResponseStats};
+use crate::stage::Stages;
+use crate::Sent;
+
+// Requests sent later than this after their intended send time are behind schedule
+pub const LATE_SEND_THRESHOLD: std::time::Duration = std::time::Duration::from_millis(10);
+
+// Stats aggregates the results of a test, independently of how they are displayed
+// Latencies are kept in milliseconds
+#[derive(Default, Clone)]
+pub struct Stats {
+    pub count: usize,
+    pub success: usize,
+    pub failed: usize,
+    pub timeouts: usize,
+    pub sent: usize,
+    pub late_sends: usize,
+    pub max_queue_delay: u64,
+    pub latencies: Latencies,
+    // Latencies measured from the intended send time, only recorded with a rate
+    pub corrected_latencies: Latencies,
+    pub status_codes: HashMap<StatusCode, usize>,
+    pub data_transfer: u64,
+    pub total_dns_lookup: u128,
+    pub total_dns_resolution: u128,
+    pub dns_count: usize,
+    pub cache_categories: HashMap<CacheCategory, usize>,
+    // Latencies of the scenario iterations, from the first step to the last one
+    pub iterations: Latencies,
+    // Iterations that stopped at a failed step
+    pub failed_iterations: usize,
+    // Passes and failures of each --check, in the order of the command line
+    pub checks: Vec<CheckCount>,
+    // Requests that failed with an error, by kind of error
+    pub errors: HashMap<ErrorKind, ErrorCount>,
+    // Time the test has been running for
+    pub duration: std::time::Duration,
+}
+
+impl Stats {
+    pub fn record(&mut self, stat: &ResponseStats) {
+        let latency = stat.duration.as_millis() as u64;
+        self.latencies.record(latency);
+
+        // Coordinated omission correction, a request that waited for a free
+        // worker is charged the time it spent behind schedule
+        if let Some(queue_delay) = stat.queue_delay {
+            self.corrected_latencies
+                .record((queue_delay + stat.duration).as_millis() as u64);
+        }
+
+        if let Some(ref cache_status) = stat.cache_status {
+            *self
+                .cache_categories
+                .entry(CacheCategory::from_cache_status(cache_status))
+                .or_insert(0) += 1;
+        }
+
+        if let Some((dns_lookup_time, dns_resolution_time)) = stat.dns_times {
+            self.total_dns_lookup += dns_lookup_time.as_millis();
+            self.total_dns_resolution += dns_resolution_time.as_millis();
+            self.dns_count += 1;
+        }
+
+        self.count += 1;
+
+        if let Some(iteration) = stat.iteration {
+            self.iterations.record(iteration.as_millis() as u64);
+            if !stat.is_success() {
+                self.failed_iterations += 1;
+            }
+        }
+
+        if self.checks.len() < stat.checks.len() {
+            self.checks.resize(stat.checks.len(), CheckCount::default());
+        }
+        for (count, passed) in self.checks.iter_mut().zip(&stat.checks) {
+            if *passed {
+                count.passed += 1;
+            } else {
+                count.failed += 1;
+            }
+        }
+
+        if let Some(error) = &stat.error {
+            self.errors
+                .entry(error.kind)
+                .or_default()
+                .record(&error.message);
+        }
+
+        if let Some(status_code) = stat.status_code {
+            *self.status_codes.entry(status_code).or_insert(0) += 1;
+            self.data_transfer += stat.content_length.unwrap_or(0);
+        }
+
+        // Only timeouts are counted apart, other errors fail the request, e.g. a refused
+        // connection. A response that fails a check is failed too, e.g. an error page
+        if stat
+            .error
+            .as_ref()
+            .is_some_and(|error| error.kind.is_timeout())
+        {
+            self.timeouts += 1;
+        } else if stat.is_success() {
+            self.success += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    pub fn record_sent(&mut self, sent: &Sent) {
+        self.sent += sent.count;
+
+        if let Some(queue_delay) = sent.queue_delay {
+            if queue_delay > LATE_SEND_THRESHOLD {
+                self.late_sends += sent.count;
+            }
+            self.max_queue_delay = self.max_queue_delay.max(queue_delay.as_millis() as u64);
+        }
+    }
+
+    pub fn min(&self) -> u64 {
+        self.latencies.min()
+    }
+
+    pub fn max(&self) -> u64 {
+        self.latencies.max()
+    }
+
+    pub fn avg(&self) -> u64 {
+        self.latencies.avg()
+    }
+
+    pub fn std_dev(&self) -> u64 {
+        self.latencies.std_dev()
+    }
+
+    /// Requests that did not succeed, in percent of the completed requests
+    pub fn error_rate(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.failed + self.timeouts) as f64 * 100.0 / self.count as f64
+    }
+
+    /// Completed requests per second
+    pub fn rps(&self) -> f64 {
+        per_second(self.count as f64, self.duration)
+    }
+
+    /// Sent requests per second
+    pub fn send_rate(&self) -> f64 {
+        per_second(self.sent as f64, self.duration)
+    }
+
+    /// Bytes received per second
+    pub fn throughput(&self) -> f64 {
+        per_second(self.data_transfer as f64, self.duration)
+    }
+
+    pub fn avg_dns_lookup(&self) -> u128 {
+        self.total_dns_lookup / self.dns_count.max(1) as u128
+    }
+
+    pub fn avg_dns_resolution(&self) -> u128 {
+        self.total_dns_resolution / self.dns_count.max(1) as u128
+    }
+
+    /// Kinds of errors with their count, the most frequent first
+    pub fn sorted_errors(&self) -> Vec<(ErrorKind, &ErrorCount)> {
+        let mut errors: Vec<_> = self
+            .errors
+            .iter()
+            .map(|(kind, count)| (*kind, count))
+            .collect();
+        errors.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(&b.0)));
+        errors
+    }
+
+    /// Requests served from cache, in percent of the completed requests
+    pub fn cache_hit_rate(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        *self.cache_categories.get(&CacheCategory::Hit).unwrap_or(&0) as f64 * 100.0
+            / self.count as f64
+    }
+}
+
+// Results of a test, overall and broken down by load profile stage and by endpoint
+#[derive(Default, Clone)]
+pub struct Results {
+    pub stats: Stats,
+    pub stages: Vec<Stats>,
+    pub profile: Option<Stages>,
+    // Stats of each --url-file endpoint or --scenario step, in the order of the file
+    pub endpoints: Vec<Stats>,
+    // Whether the test runs scenario iterations rather than single requests
+    pub scenario: bool,
+}
+
+impl Results {
+    pub fn new(args: &Cli) -> Self {
+        let profile = args.load_profile();
+        Self {
+            stats: Stats::default(),
+            endpoints: vec![Stats::default(); args.endpoint_labels().len()],
+            scenario: args.is_scenario(),
+            stages: profile
+                .as_ref()
+                .map(|stages| vec![Stats::default(); stages.stages.len()])
+                .unwrap_or_default(),
+            profile,
+        }
+    }
+
+    pub fn record(&mut self, stat: &ResponseStats) {
+        self.stats.record(stat);
+
+        if let Some(stage_stats) = stat.stage.and_then(|i| self.stages.get_mut(i)) {
+            stage_stats.record(stat);
+        }
+        if let Some(endpoint_stats) = stat.endpoint.and_then(|i| self.endpoints.get_mut(i)) {
+            endpoint_stats.record(stat);
+        }
+    }
+
+    pub fn record_sent(&mut self, sent: &Sent) {
+        self.stats.record_sent(sent);
+    }
+
+    /// Completed units of the test: scenario iterations, or requests
+    pub fn completed(&self) -> usize {
+        if self.scenario {
+            self.stats.iterations.len()
+        } else {
+            self.stats.count
+        }
+    }
+
+    /// Set the time the test has been running for
+    /// Each stage gets the part of its duration that has run
+    pub fn set_duration(&mut self, duration: std::time::Duration) {
+        self.stats.duration = duration;
+        for stats in &mut self.endpoints {
+            stats.duration = duration;
+        }
+
+        if let Some(profile) = &self.profile {
+            let mut stage_start = std::time::Duration::ZERO;
+            for (stage, stats) in profile.stages.iter().zip(self.stages.iter_mut()) {
+                stats.duration = duration.saturating_sub(stage_start).min(stage.duration);
+                stage_start += stage.duration;
+            }
+        }
+    }
+}
+
+fn per_second(value: f64, duration: std::time::Duration) -> f64 {
+    if duration.is_zero() {
+        return 0.0;
+    }
+    value / duration.as_secs_f64()
+}
+
+// Latencies above it are recorded as it, in milliseconds
+const MAX_LATENCY: u64 = 60 * 60 * 1000;
+
+// Latencies is a histogram of latencies in milliseconds, with 3 significant digits
+// Its memory does not grow with the requests, it is allocated with the first latency
+#[derive(Default, Clone)]
+pub struct Latencies(Option<Histogram<u64>>);
+
+impl Latencies {
+    pub fn record(&mut self, latency: u64) {
+        self.0
+            .get_or_insert_with(|| {
+                Histogram::new_with_bounds(1, MAX_LATENCY, 3).expect("valid histogram bounds")
+            })
+            .saturating_record(latency.min(MAX_LATENCY));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0
+            .as_ref()
+            .map_or(0, |histogram| histogram.len() as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn min(&self) -> u64 {
+        self.0.as_ref().map_or(0, |histogram| histogram.min())
+    }
+
+    pub fn max(&self) -> u64 {
+        self.0.as_ref().map_or(0, |histogram| histogram.max())
+    }
+
+    pub fn avg(&self) -> u64 {
+        self.0
+            .as_ref()
+            .map_or(0, |histogram| histogram.mean().round() as u64)
+    }
+
+    pub fn std_dev(&self) -> u64 {
+        self.0
+            .as_ref()
+            .map_or(0, |histogram| histogram.stdev().round() as u64)
+    }
+
+    /// Get the latency at the given percentile, 0 when there are no latencies
+    pub fn percentile(&self, p: f64) -> u64 {
+        self.0
+            .as_ref()
+            .map_or(0, |histogram| histogram.value_at_quantile(p / 100.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latencies_are_empty_by_default() {
+        let latencies = Latencies::default();
+        assert!(latencies.is_empty());
+        assert_eq!(latencies.min(), 0);
+        assert_eq!(latencies.max(), 0);
+        assert_eq!(latencies.avg(), 0);
+        assert_eq!(latencies.percentile(99.0), 0);
+    }
+
+    #[test]
+    fn latencies_percentiles() {
+        let mut latencies = Latencies::default();
+        for latency in 1..=100 {
+            latencies.record(latency);
+        }
+        assert_eq!(latencies.len(), 100);
+        assert_eq!(latencies.min(), 1);
+        assert_eq!(latencies.max(), 100);
+        assert_eq!(latencies.avg(), 51);
+        assert_eq!(latencies.percentile(0.0), 1);
+        assert_eq!(latencies.percentile(50.0), 50);
+        assert_eq!(latencies.percentile(99.0), 99);
+        assert_eq!(latencies.percentile(100.0), 100);
+    }
+
+    #[test]
+    fn latencies_keep_large_values() {
+        let mut latencies = Latencies::default();
+        latencies.record(0);
+        latencies.record(5);
+        latencies.record(1_234_567);
+        latencies.record(u64::MAX);
+        assert_eq!(latencies.len(), 4);
+        assert_eq!(latencies.min(), 0);
+        assert_eq!(latencies.percentile(50.0), 5);
+        // 3 significant digits
+        assert!(latencies.percentile(75.0).abs_diff(1_234_567) <= 1_235);
+        assert!(latencies.max().abs_diff(MAX_LATENCY) <= MAX_LATENCY / 1000);
+    }
+}
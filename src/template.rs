@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+// Variables available to templates, e.g. the columns of a data file
+pub type Vars = HashMap<String, String>;
+
+// Expression of a template, evaluated for every request
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Uuid,
+    Seq,
+    Now,
+    NowMs,
+    RandInt(i64, i64),
+    RandChoice(Vec<String>),
+    RandString(usize),
+    Var(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Expr(Expr),
+}
+
+// Template is a string with {{...}} expressions, e.g. /users/{{randInt 1 1000}}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+// Context of the request a template is rendered for
+pub struct Context<'a> {
+    // Index of the request in the test, starting at 0
+    pub seq: u64,
+    pub vars: &'a Vars,
+}
+
+impl Expr {
+    fn parse(input: &str) -> Result<Self, String> {
+        let mut words = input.split_whitespace();
+        let name = words
+            .next()
+            .ok_or_else(|| "Empty template expression {{}}".to_string())?;
+        let args: Vec<&str> = words.collect();
+        let invalid = |usage: &str| {
+            format!(
+                "Invalid template expression {{{{{}}}}}, expected {{{{{}}}}}",
+                input.trim(),
+                usage
+            )
+        };
+
+        let expr = match (name, args.as_slice()) {
+            ("uuid", []) => Expr::Uuid,
+            ("seq", []) => Expr::Seq,
+            ("now", []) => Expr::Now,
+            ("now_ms", []) => Expr::NowMs,
+            ("randInt", [min, max]) => {
+                let min: i64 = min.parse().map_err(|_| invalid("randInt min max"))?;
+                let max: i64 = max.parse().map_err(|_| invalid("randInt min max"))?;
+                if min > max {
+                    return Err(invalid("randInt min max"));
+                }
+                Expr::RandInt(min, max)
+            }
+            ("randInt", _) => return Err(invalid("randInt min max")),
+            ("randChoice", []) => return Err(invalid("randChoice a b c")),
+            ("randChoice", choices) => {
+                Expr::RandChoice(choices.iter().map(|choice| choice.to_string()).collect())
+            }
+            ("randString", [length]) => {
+                Expr::RandString(length.parse().map_err(|_| invalid("randString length"))?)
+            }
+            ("randString", _) => return Err(invalid("randString length")),
+            (name, []) => Expr::Var(name.to_string()),
+            (name, _) => return Err(format!("Unknown template function: {}", name)),
+        };
+        Ok(expr)
+    }
+
+    fn render(&self, context: &Context, output: &mut String) {
+        let now = || {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+        };
+
+        match self {
+            Expr::Uuid => output.push_str(&uuid_v4()),
+            Expr::Seq => output.push_str(&context.seq.to_string()),
+            Expr::Now => output.push_str(&now().as_secs().to_string()),
+            Expr::NowMs => output.push_str(&now().as_millis().to_string()),
+            Expr::RandInt(min, max) => output.push_str(&fastrand::i64(*min..=*max).to_string()),
+            Expr::RandChoice(choices) => {
+                output.push_str(&choices[fastrand::usize(..choices.len())])
+            }
+            Expr::RandString(length) => {
+                output.extend((0..*length).map(|_| fastrand::alphanumeric()))
+            }
+            // Unknown variables render as an empty string
+            Expr::Var(name) => {
+                if let Some(value) = context.vars.get(name) {
+                    output.push_str(value)
+                }
+            }
+        }
+    }
+}
+
+impl Template {
+    /// Parse a template, text outside of {{...}} is kept as is
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = input;
+
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..]
+                .find("}}")
+                .map(|end| start + end)
+                .ok_or_else(|| format!("Unclosed template expression in: {}", input))?;
+
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            parts.push(Part::Expr(Expr::parse(&rest[start + 2..end])?));
+            rest = &rest[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Whether the template renders the same string for every request
+    pub fn is_static(&self) -> bool {
+        self.parts
+            .iter()
+            .all(|part| matches!(part, Part::Literal(_)))
+    }
+
+    pub fn render(&self, context: &Context) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Expr(expr) => expr.render(context, &mut output),
+            }
+        }
+        output
+    }
+}
+
+/// Parse a template, None when it has no expression and renders as is
+pub fn parse_dynamic(input: &str) -> Result<Option<Template>, String> {
+    let template = Template::parse(input)?;
+    Ok((!template.is_static()).then_some(template))
+}
+
+/// Random version 4 UUID
+fn uuid_v4() -> String {
+    let bits = (fastrand::u128(..) & !(0xf << 76) & !(0x3 << 62)) | (0x4 << 76) | (0x2 << 62);
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(input: &str, seq: u64, vars: &Vars) -> String {
+        Template::parse(input)
+            .unwrap()
+            .render(&Context { seq, vars })
+    }
+
+    #[test]
+    fn literals_and_variables() {
+        let vars = Vars::from([("id".to_string(), "42".to_string())]);
+        assert_eq!(render("/users/{{id}}?n={{seq}}", 7, &vars), "/users/42?n=7");
+        assert_eq!(render("{{ id }}", 0, &vars), "42");
+        assert_eq!(render("{{missing}}", 0, &vars), "");
+        assert_eq!(render("no expression", 0, &vars), "no expression");
+    }
+
+    #[test]
+    fn random_functions_stay_in_range() {
+        let vars = Vars::new();
+        for _ in 0..100 {
+            let value: i64 = render("{{randInt -2 2}}", 0, &vars).parse().unwrap();
+            assert!((-2..=2).contains(&value));
+            assert!(["a", "b"].contains(&render("{{randChoice a b}}", 0, &vars).as_str()));
+        }
+        let string = render("{{randString 12}}", 0, &vars);
+        assert_eq!(string.len(), 12);
+        assert!(string.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn uuids_are_version_4() {
+        let uuid = render("{{uuid}}", 0, &Vars::new());
+        let groups: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(
+            groups.iter().map(|group| group.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert!(groups[2].starts_with('4'));
+        assert!(matches!(
+            groups[3].chars().next(),
+            Some('8'..='9' | 'a'..='b')
+        ));
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for input in [
+            "{{}}",
+            "{{seq",
+            "{{randInt 5 1}}",
+            "{{randInt a 1}}",
+            "{{randChoice}}",
+            "{{randString}}",
+            "{{unknown 1}}",
+        ] {
+            assert!(Template::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn static_templates_are_not_dynamic() {
+        assert_eq!(parse_dynamic("/users/1"), Ok(None));
+        assert!(parse_dynamic("/users/{{seq}}").unwrap().is_some());
+    }
+}
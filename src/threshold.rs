@@ -0,0 +1,342 @@
+use serde::Serialize;
+
+use crate::stats::Stats;
+
+// Metric is a value computed from the statistics of a test
+// Latencies are in milliseconds and rates in percent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Percentile(f64),
+    CorrectedPercentile(f64),
+    Min,
+    Max,
+    Avg,
+    StdDev,
+    ErrorRate,
+    SuccessRate,
+    Rps,
+    Requests,
+    Failed,
+    Timeouts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+// Threshold is a condition on a metric, e.g. p99<300ms or error_rate<1%
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    pub expression: String,
+    metric: Metric,
+    operator: Operator,
+    value: f64,
+}
+
+// Outcome of a --fail-if threshold, breached when its condition holds
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    pub expression: String,
+    pub observed: String,
+    pub breached: bool,
+}
+
+impl Metric {
+    fn parse(name: &str) -> Result<Self, String> {
+        let name = name.trim().to_lowercase();
+        let metric = match name.as_str() {
+            "min" => Metric::Min,
+            "max" => Metric::Max,
+            "avg" | "mean" => Metric::Avg,
+            "stddev" | "std_dev" => Metric::StdDev,
+            "error_rate" => Metric::ErrorRate,
+            "success_rate" => Metric::SuccessRate,
+            "rps" => Metric::Rps,
+            "requests" => Metric::Requests,
+            "failed" => Metric::Failed,
+            "timeouts" => Metric::Timeouts,
+            "median" => Metric::Percentile(50.0),
+            _ => {
+                let (corrected, p) = match name.strip_prefix("corrected_") {
+                    Some(p) => (true, p),
+                    None => (false, name.as_str()),
+                };
+                let p: f64 = p
+                    .strip_prefix('p')
+                    .and_then(|p| p.parse().ok())
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or_else(|| format!("Unknown metric: {}", name))?;
+                if corrected {
+                    Metric::CorrectedPercentile(p)
+                } else {
+                    Metric::Percentile(p)
+                }
+            }
+        };
+        Ok(metric)
+    }
+
+    fn is_latency(&self) -> bool {
+        matches!(
+            self,
+            Metric::Percentile(_)
+                | Metric::CorrectedPercentile(_)
+                | Metric::Min
+                | Metric::Max
+                | Metric::Avg
+                | Metric::StdDev
+        )
+    }
+
+    fn is_rate(&self) -> bool {
+        matches!(self, Metric::ErrorRate | Metric::SuccessRate)
+    }
+
+    /// Parse a value in the unit of the metric
+    /// Latencies accept us, ms, s and m and default to ms, rates accept an optional %
+    fn parse_value(&self, value: &str) -> Result<f64, String> {
+        let value = value.trim();
+        let invalid = || format!("Invalid value: {}", value);
+
+        if self.is_latency() {
+            let (number, factor) = [("us", 0.001), ("ms", 1.0), ("s", 1000.0), ("m", 60000.0)]
+                .iter()
+                .find_map(|(unit, factor)| value.strip_suffix(unit).map(|n| (n, *factor)))
+                .unwrap_or((value, 1.0));
+            return number
+                .trim()
+                .parse::<f64>()
+                .map(|n| n * factor)
+                .map_err(|_| invalid());
+        }
+
+        let number = if self.is_rate() {
+            value.strip_suffix('%').unwrap_or(value)
+        } else {
+            value
+        };
+        number.trim().parse::<f64>().map_err(|_| invalid())
+    }
+
+    /// Value of the metric for the given statistics
+    pub fn value(&self, stats: &Stats) -> f64 {
+        match self {
+            Metric::Percentile(p) => stats.latencies.percentile(*p) as f64,
+            Metric::CorrectedPercentile(p) => stats.corrected_latencies.percentile(*p) as f64,
+            Metric::Min => stats.min() as f64,
+            Metric::Max => stats.max() as f64,
+            Metric::Avg => stats.avg() as f64,
+            Metric::StdDev => stats.std_dev() as f64,
+            Metric::ErrorRate => stats.error_rate(),
+            Metric::SuccessRate => {
+                if stats.count == 0 {
+                    0.0
+                } else {
+                    100.0 - stats.error_rate()
+                }
+            }
+            Metric::Rps => stats.rps(),
+            Metric::Requests => stats.count as f64,
+            Metric::Failed => stats.failed as f64,
+            Metric::Timeouts => stats.timeouts as f64,
+        }
+    }
+
+    /// Format a value of the metric with its unit
+    pub fn format_value(&self, value: f64) -> String {
+        if self.is_latency() {
+            format!("{:.0}ms", value)
+        } else if self.is_rate() {
+            format!("{:.2}%", value)
+        } else if *self == Metric::Rps {
+            format!("{:.1}", value)
+        } else {
+            format!("{:.0}", value)
+        }
+    }
+}
+
+impl Operator {
+    fn apply(&self, left: f64, right: f64) -> bool {
+        match self {
+            Operator::Lt => left < right,
+            Operator::Le => left <= right,
+            Operator::Gt => left > right,
+            Operator::Ge => left >= right,
+            Operator::Eq => left == right,
+            Operator::Ne => left != right,
+        }
+    }
+}
+
+impl Threshold {
+    /// Parse a threshold expression, e.g. p95>250ms, error_rate<1% or rps>=500
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let operators = [
+            ("<=", Operator::Le),
+            (">=", Operator::Ge),
+            ("==", Operator::Eq),
+            ("!=", Operator::Ne),
+            ("<", Operator::Lt),
+            (">", Operator::Gt),
+        ];
+
+        let (position, symbol, operator) = operators
+            .iter()
+            .filter_map(|(symbol, operator)| {
+                input
+                    .find(symbol)
+                    .map(|position| (position, *symbol, *operator))
+            })
+            .min_by_key(|(position, _, _)| *position)
+            .ok_or_else(|| format!("Missing operator in threshold: {}", input))?;
+
+        let metric = Metric::parse(&input[..position])?;
+        let value = metric
+            .parse_value(&input[position + symbol.len()..])
+            .map_err(|e| format!("{} in threshold: {}", e, input))?;
+
+        Ok(Self {
+            expression: input.trim().to_string(),
+            metric,
+            operator,
+            value,
+        })
+    }
+
+    /// Observed value of the metric, formatted with its unit
+    pub fn observed(&self, stats: &Stats) -> String {
+        self.metric.format_value(self.metric.value(stats))
+    }
+
+    /// Whether the condition holds for the given statistics
+    pub fn holds(&self, stats: &Stats) -> bool {
+        self.operator.apply(self.metric.value(stats), self.value)
+    }
+}
+
+/// Evaluate --fail-if thresholds against the final statistics of a test
+pub fn evaluate(thresholds: &[Threshold], stats: &Stats) -> Vec<Outcome> {
+    thresholds
+        .iter()
+        .map(|threshold| Outcome {
+            expression: threshold.expression.clone(),
+            observed: threshold.observed(stats),
+            breached: threshold.holds(stats),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> Stats {
+        let mut stats = Stats {
+            count: 100,
+            success: 98,
+            failed: 1,
+            timeouts: 1,
+            duration: std::time::Duration::from_secs(10),
+            ..Stats::default()
+        };
+        for latency in 1..=100 {
+            stats.latencies.record(latency);
+        }
+        stats
+    }
+
+    #[test]
+    fn metrics_and_units() {
+        let cases = [
+            ("p99<300ms", Metric::Percentile(99.0), Operator::Lt, 300.0),
+            (
+                "p99.9 <= 1s",
+                Metric::Percentile(99.9),
+                Operator::Le,
+                1000.0,
+            ),
+            ("median>500us", Metric::Percentile(50.0), Operator::Gt, 0.5),
+            (
+                "corrected_p95>=2m",
+                Metric::CorrectedPercentile(95.0),
+                Operator::Ge,
+                120000.0,
+            ),
+            ("AVG!=10", Metric::Avg, Operator::Ne, 10.0),
+            ("error_rate<1%", Metric::ErrorRate, Operator::Lt, 1.0),
+            (
+                "success_rate>=99.5",
+                Metric::SuccessRate,
+                Operator::Ge,
+                99.5,
+            ),
+            ("rps==500", Metric::Rps, Operator::Eq, 500.0),
+        ];
+        for (input, metric, operator, value) in cases {
+            let threshold = Threshold::parse(input).unwrap();
+            assert_eq!(
+                (threshold.metric, threshold.operator, threshold.value),
+                (metric, operator, value),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_thresholds_are_rejected() {
+        for input in [
+            "p99",
+            "p101<1",
+            "latency<1",
+            "p99<fast",
+            "error_rate<1ms",
+            "rps>1%",
+        ] {
+            assert!(Threshold::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn thresholds_hold_against_stats() {
+        let stats = stats();
+        for (input, holds) in [
+            ("p99<100ms", true),
+            ("p50>50", false),
+            ("max==100", true),
+            ("error_rate<2%", false),
+            ("error_rate<=2%", true),
+            ("success_rate>=98%", true),
+            ("rps>=10", true),
+            ("timeouts>0", true),
+            ("requests!=100", false),
+        ] {
+            assert_eq!(
+                Threshold::parse(input).unwrap().holds(&stats),
+                holds,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn outcomes_report_the_observed_values() {
+        let thresholds = [
+            Threshold::parse("p99>50ms").unwrap(),
+            Threshold::parse("error_rate>5%").unwrap(),
+        ];
+        let outcomes = evaluate(&thresholds, &stats());
+        assert_eq!(outcomes[0].observed, "99ms");
+        assert!(outcomes[0].breached);
+        assert_eq!(outcomes[1].observed, "2.00%");
+        assert!(!outcomes[1].breached);
+    }
+}
@@ -1,15 +1,15 @@
-use std::{collections::BTreeMap, collections::HashMap, num::NonZeroUsize};
+use std::{collections::BTreeMap, num::NonZeroUsize, sync::Arc};
 
 use crossterm::{
     event::{self, Event, KeyCode},
     terminal::enable_raw_mode,
 };
 use gethostname::gethostname;
-use ratatui::widgets::ListItem;
+use ratatui::widgets::{Cell, ListItem, Row, Table};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
-    widgets::{Bar, BarChart, BarGroup, Block, Borders, List, Paragraph},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, List, Paragraph, Wrap},
     Frame, Terminal,
 };
 use ratatui::{
@@ -20,7 +20,11 @@ use reqwest::StatusCode;
 use std::thread::available_parallelism;
 use tokio::sync::mpsc;
 
-use crate::cache::CacheCategory;
+use crate::check::StatusSet;
+use crate::schema::Schema;
+use crate::stage::{StageUnit, Stages};
+use crate::stats::{Latencies, Results, Stats};
+use crate::utils::format_duration;
 use crate::ResponseStats;
 use crate::{Cli, Sent};
 
@@ -32,126 +36,102 @@ const LOGO: &str = r#"
     ██║     ███████╗██║     ███████╗
     ╚═╝     ╚══════╝╚═╝     ╚══════╝"#;
 
-#[derive(Default)]
-struct Stats {
-    count: usize,
-    success: usize,
-    failed: usize,
-    timeouts: usize,
-    sent: usize,
-    min: u64,
-    max: u64,
-    avg: u64,
-    std_dev: u64,
-    rps: u64,
-    data: u64,
-    total_dns_lookup: Vec<u128>,
-    total_dns_resolution: Vec<u128>,
-    avg_dns_lookup: u128,
-    avg_dns_resolution: u128,
-    cache_categories: HashMap<CacheCategory, usize>,
+// Column the endpoints table is sorted by, numbers in descending order
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EndpointSort {
+    // Order of the --url-file
+    File,
+    Requests,
+    Errors,
+    Avg,
+    P99,
+    Data,
 }
+
+impl EndpointSort {
+    fn next(self) -> Self {
+        match self {
+            EndpointSort::File => EndpointSort::Requests,
+            EndpointSort::Requests => EndpointSort::Errors,
+            EndpointSort::Errors => EndpointSort::Avg,
+            EndpointSort::Avg => EndpointSort::P99,
+            EndpointSort::P99 => EndpointSort::Data,
+            EndpointSort::Data => EndpointSort::File,
+        }
+    }
+
+    fn key(self, stats: &Stats) -> u64 {
+        match self {
+            EndpointSort::File => 0,
+            EndpointSort::Requests => stats.count as u64,
+            EndpointSort::Errors => (stats.failed + stats.timeouts) as u64,
+            EndpointSort::Avg => stats.avg(),
+            EndpointSort::P99 => stats.latencies.percentile(99.0),
+            EndpointSort::Data => stats.data_transfer,
+        }
+    }
+}
+
 pub struct Dashboard {
     label_storage: Vec<String>,
     bar_chart_data: Vec<(String, u64)>,
-    histogram: Vec<(String, u64)>,
     requests: Vec<ResponseStats>,
     args: Cli,
-    status_codes: HashMap<StatusCode, usize>,
-    stats: Stats,
+    results: Results,
     elapsed: std::time::Instant,
     final_duration: Option<std::time::Duration>,
-    data_transfer: f64,
+    run_duration: Option<std::time::Duration>,
+    stages: Option<Stages>,
+    // Credentials with the secrets redacted
+    auth: Option<String>,
+    // Endpoint of --url-file the charts and the request log show, all when None
+    filter: Option<usize>,
+    sort: EndpointSort,
+    // Labels of the endpoints, e.g. GET /search
+    endpoints: Vec<String>,
+    // Expressions of the --check conditions
+    checks: Vec<String>,
+    // Status codes of a successful response
+    expect_status: StatusSet,
+    finished: bool,
 }
 
 impl Dashboard {
+    pub fn results(&self) -> &Results {
+        &self.results
+    }
+
     fn update_stats(&mut self, stat: ResponseStats) {
-        // Update histogram
-        self.histogram.push((
-            format!("{:?}", stat.status_code),
-            stat.duration.as_millis() as u64,
-        ));
+        self.results.record(&stat);
 
         // Update requests
         if self.requests.len() == 100 {
             self.requests.remove(0);
         }
-        self.requests.push(stat.clone());
-
-        // Update cache categories
-        if let Some(ref cache_status) = stat.cache_status {
-            *self
-                .stats
-                .cache_categories
-                .entry(CacheCategory::from_cache_status(&cache_status))
-                .or_insert(0) += 1;
-        }
-
-        let dns_times = stat.dns_times;
-        if let Some(dns_times) = dns_times {
-            let (dns_lookup_time, dns_resolution_time) = dns_times;
-            self.stats
-                .total_dns_lookup
-                .push(dns_lookup_time.as_millis());
-            self.stats
-                .total_dns_resolution
-                .push(dns_resolution_time.as_millis());
-            self.stats.avg_dns_lookup = self.stats.total_dns_lookup.iter().sum::<u128>()
-                / self.stats.total_dns_lookup.len() as u128;
-            self.stats.avg_dns_resolution = self.stats.total_dns_resolution.iter().sum::<u128>()
-                / self.stats.total_dns_resolution.len() as u128;
-        }
-
-        if self.stats.min == 0 || stat.duration.as_millis() < self.stats.min.into() {
-            self.stats.min = stat.duration.as_millis() as u64;
-        }
-
-        if stat.duration.as_millis() > self.stats.max.into() {
-            self.stats.max = stat.duration.as_millis() as u64;
-        }
-
-        self.stats.rps = if self.elapsed.elapsed().as_secs() > 0 {
-            (self.stats.count as f64 / self.elapsed.elapsed().as_secs() as f64) as u64
-        } else {
-            0
-        };
-
-        self.stats.data = (self.data_transfer / self.elapsed.elapsed().as_secs() as f64) as u64;
-
-        // If status code is None, it means the request timed out
-        if stat.status_code.is_none() {
-            self.stats.timeouts += 1;
-            self.stats.count += 1;
-            self.requests.push(stat);
-            return;
-        }
-
-        let status_code = stat.status_code.unwrap();
-
-        // Update status codes
-        *self.status_codes.entry(status_code).or_insert(0) += 1;
-        self.data_transfer += stat.content_length.unwrap_or(0) as f64;
-
-        // Update stats
-        self.stats.count += 1;
-        if status_code.is_success() {
-            self.stats.success += 1;
-        } else {
-            self.stats.failed += 1;
-        }
+        self.requests.push(stat);
     }
 
     pub fn new(args: Cli) -> Self {
         Self {
             bar_chart_data: Vec::new(),
-            histogram: Vec::new(),
-            requests: Vec::with_capacity(args.number as usize),
-            status_codes: HashMap::new(),
-            stats: Stats::default(),
+            requests: Vec::with_capacity(100),
+            results: Results::new(&args),
             elapsed: std::time::Instant::now(),
             label_storage: Vec::with_capacity(10),
-            data_transfer: 0.0,
             final_duration: None,
+            run_duration: args.run_duration(),
+            stages: args.load_profile(),
+            auth: args.auth().ok().flatten().map(|auth| auth.describe()),
+            filter: None,
+            sort: EndpointSort::File,
+            endpoints: args.endpoint_labels(),
+            checks: args
+                .checks()
+                .into_iter()
+                .map(|check| check.expression)
+                .collect(),
+            expect_status: args.expect_status(),
+            finished: false,
             args,
         }
     }
@@ -167,22 +147,36 @@ impl Dashboard {
         terminal.clear()?;
 
         loop {
-            // Update stats
-            while let Ok(stat) = rx.try_recv() {
-                self.update_stats(stat);
+            // Update stats, the channel is closed once every request has completed
+            loop {
+                match rx.try_recv() {
+                    Ok(stat) => self.update_stats(stat),
+                    Err(mpsc::error::TryRecvError::Empty) => break,
+                    Err(mpsc::error::TryRecvError::Disconnected) => {
+                        self.finished = true;
+                        break;
+                    }
+                }
             }
 
             while let Ok(sent) = sent_rx.try_recv() {
                 self.update_sent(sent);
             }
 
+            self.results
+                .set_duration(self.final_duration.unwrap_or(self.elapsed.elapsed()));
+
             terminal.draw(|f| {
                 self.render_layout(f);
             })?;
 
             if event::poll(std::time::Duration::from_millis(25))? {
                 if let Event::Key(key) = event::read()? {
-                    if matches!(
+                    if key.code == KeyCode::Char('e') {
+                        self.next_filter();
+                    } else if key.code == KeyCode::Char('s') {
+                        self.sort = self.sort.next();
+                    } else if matches!(
                         key.code,
                         KeyCode::Char('q')
                             | KeyCode::Char('r')
@@ -198,45 +192,77 @@ impl Dashboard {
         }
     }
 
+    /// Show the next endpoint, then all of them again after the last one
+    fn next_filter(&mut self) {
+        self.filter = match self.filter {
+            None if !self.results.endpoints.is_empty() => Some(0),
+            Some(i) if i + 1 < self.results.endpoints.len() => Some(i + 1),
+            _ => None,
+        };
+    }
+
+    /// Stats of the endpoint shown, or of the whole test
+    fn view(&self) -> &Stats {
+        self.filter
+            .and_then(|i| self.results.endpoints.get(i))
+            .unwrap_or(&self.results.stats)
+    }
+
+    /// Title of a panel, with the endpoint shown
+    fn view_title(&self, title: &str) -> String {
+        match self.filter {
+            Some(i) => format!("{} [{}]", title, self.endpoints[i]),
+            None => title.to_string(),
+        }
+    }
+
     fn update_sent(&mut self, sent: Sent) {
-        self.stats.sent += sent.count;
+        self.results.record_sent(&sent);
     }
 
-    fn calculate_stats(&mut self, latencies: &[u64]) {
-        if latencies.is_empty() {
-            return;
+    /// Actual send rate against the target rate, e.g. 98/100
+    fn send_rate(&self, target: f64) -> String {
+        let actual = self.results.stats.send_rate();
+
+        if self.results.stats.late_sends > 0 {
+            format!(
+                "{:.0}/{:.0} behind: {} late, max {}ms",
+                actual, target, self.results.stats.late_sends, self.results.stats.max_queue_delay
+            )
+        } else {
+            format!("{:.0}/{:.0}", actual, target)
         }
+    }
 
-        // sort the latencies
-        let mut latencies = latencies.to_vec();
-        latencies.sort_unstable();
+    /// Whether every request of the test has completed
+    fn is_finished(&self) -> bool {
+        self.finished
+            || (self.run_duration.is_none()
+                && self.results.completed() >= self.args.number as usize)
+    }
 
-        // Calculate standard deviation
-        let variance = latencies
-            .iter()
-            .map(|&x| {
-                let diff = x as i64 - self.stats.avg as i64;
-                (diff * diff) as u64
-            })
-            .sum::<u64>()
-            / self.stats.count as u64;
-        let std_dev = (variance as f64).sqrt() as u64;
-        self.stats.std_dev = std_dev;
-
-        // Calculate average
-        let avg = latencies.iter().sum::<u64>() / self.stats.count as u64;
-        self.stats.avg = avg;
+    /// Progress of the test in percent, time-based when a duration is set
+    fn progress(&self) -> usize {
+        if self.is_finished() {
+            return 100;
+        }
+
+        match self.run_duration {
+            Some(duration) => {
+                let elapsed = self.elapsed.elapsed().as_millis();
+                // Stay below 100% while in-flight requests are draining
+                ((elapsed * 100) / duration.as_millis().max(1)).min(99) as usize
+            }
+            None => (self.results.completed() * 100) / (self.args.number as usize).max(1),
+        }
     }
 
     fn format_request_item(&self, stat: &ResponseStats) -> ListItem {
         if stat.status_code.is_none() {
             return ListItem::new(Line::from(vec![
-                Span::styled("[TIMEOUT]", Style::default().fg(Color::Red)),
+                Span::styled(status_text(stat), Style::default().fg(Color::Red)),
                 Span::raw(" "),
-                Span::styled(
-                    format!("{:?}", self.args.method),
-                    Style::default().fg(Color::Magenta),
-                ),
+                Span::styled(stat.method.clone(), Style::default().fg(Color::Magenta)),
                 Span::raw(" "),
                 Span::styled(
                     format!("{:.2}ms", stat.duration.as_millis()),
@@ -248,16 +274,13 @@ impl Dashboard {
                     Style::default().fg(Color::Blue),
                 ),
                 Span::raw(" "),
-                Span::styled(
-                    format!("{:?}", self.args.url),
-                    Style::default().fg(Color::White),
-                ),
+                Span::styled(stat.url.clone(), Style::default().fg(Color::White)),
             ]));
         }
 
         let status_code = stat.status_code.unwrap();
 
-        let style = if status_code.is_success() {
+        let style = if stat.is_success() {
             Style::default().fg(Color::Green)
         } else {
             Style::default().fg(Color::Red)
@@ -266,10 +289,7 @@ impl Dashboard {
         ListItem::new(Line::from(vec![
             Span::styled(format!("[{}]", status_code), style),
             Span::raw(" "),
-            Span::styled(
-                format!("{}", self.args.method),
-                Style::default().fg(Color::Magenta),
-            ),
+            Span::styled(stat.method.clone(), Style::default().fg(Color::Magenta)),
             Span::raw(" "),
             Span::styled(
                 format!("{:.2}ms", stat.duration.as_millis()),
@@ -281,43 +301,27 @@ impl Dashboard {
                 Style::default().fg(Color::Blue),
             ),
             Span::raw(" "),
-            Span::styled(
-                format!("{}", self.args.url),
-                Style::default().fg(Color::White),
-            ),
+            Span::styled(stat.url.clone(), Style::default().fg(Color::White)),
         ]))
     }
 
     fn render_latency_distribution<'a>(
         &'a mut self, // Changed to &mut self to modify label_storage
-        latencies: &[u64],
+        latencies: &Latencies,
         area_width: u16,
     ) -> BarChart<'a> {
         let percentiles = [0, 10, 25, 50, 75, 90, 95, 99, 100];
-        let mut sorted_latencies = latencies.to_vec();
-        sorted_latencies.sort_unstable();
 
         // Clear previous storage
-        self.bar_chart_data = if !sorted_latencies.is_empty() {
+        self.bar_chart_data = if !latencies.is_empty() {
             percentiles
                 .iter()
                 .map(|&p| {
-                    let idx = if p == 0 {
-                        0
-                    } else if p == 100 {
-                        sorted_latencies.len() - 1
-                    } else {
-                        let idx_float = (p as f64 / 100.0) * (sorted_latencies.len() - 1) as f64;
-                        (idx_float.round() as usize).min(sorted_latencies.len() - 1)
-                    };
-
-                    let ms = sorted_latencies[idx] as f64 / 1000.0;
+                    let latency = latencies.percentile(p as f64);
+                    let ms = latency as f64 / 1000.0;
                     let label = format!("P{:02}: {:.2}s", p, ms);
                     self.label_storage.push(label);
-                    (
-                        self.label_storage.last().unwrap().clone(),
-                        sorted_latencies[idx],
-                    )
+                    (self.label_storage.last().unwrap().clone(), latency)
                 })
                 .collect::<Vec<_>>()
         } else {
@@ -344,11 +348,43 @@ impl Dashboard {
             .label_style(Style::default().fg(Color::White))
             .block(
                 Block::default()
-                    .title("Latency Distribution")
+                    .title(self.view_title("Latency Distribution"))
                     .borders(Borders::ALL),
             )
     }
 
+    fn render_tail_latency(&self, f: &mut Frame, area: Rect, latencies: &Latencies) {
+        let mut lines = vec![Line::from(vec![
+            Span::styled("Uncorrected ", Style::default().fg(Color::Yellow)),
+            Span::raw(format!(
+                "P99: {}ms  P99.9: {}ms",
+                latencies.percentile(99.0),
+                latencies.percentile(99.9)
+            )),
+        ])];
+
+        if self.args.is_open_model() {
+            let corrected = &self.view().corrected_latencies;
+            lines.push(Line::from(vec![
+                Span::styled("Corrected   ", Style::default().fg(Color::Yellow)),
+                Span::raw(format!(
+                    "P99: {}ms  P99.9: {}ms",
+                    corrected.percentile(99.0),
+                    corrected.percentile(99.9)
+                )),
+            ]));
+        }
+
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .title(self.view_title("Tail Latency"))
+                    .borders(Borders::ALL),
+            ),
+            area,
+        );
+    }
+
     fn render_header(&self, f: &mut Frame, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
@@ -376,7 +412,7 @@ impl Dashboard {
         );
 
         // Commands section
-        let commands: Vec<Line<'_>> = vec![
+        let mut commands: Vec<Line<'_>> = vec![
             Line::from(vec![
                 Span::styled("Quit: ", Style::default().fg(Color::Yellow)),
                 Span::raw("q"),
@@ -390,6 +426,16 @@ impl Dashboard {
                 Span::raw("i"),
             ]),
         ];
+        if !self.endpoints.is_empty() {
+            commands.push(Line::from(vec![
+                Span::styled("Endpoint: ", Style::default().fg(Color::Yellow)),
+                Span::raw("e"),
+            ]));
+            commands.push(Line::from(vec![
+                Span::styled("Sort: ", Style::default().fg(Color::Yellow)),
+                Span::raw("s"),
+            ]));
+        }
 
         f.render_widget(
             Paragraph::new(commands).block(
@@ -444,10 +490,13 @@ impl Dashboard {
         );
 
         // Parameters section
-        let params = vec![
+        let mut params = vec![
             Line::from(vec![
                 Span::styled("URL: ", Style::default().fg(Color::Yellow)),
-                Span::raw(&self.args.url),
+                Span::raw(match &self.args.url_file {
+                    Some(_) => self.args.target(),
+                    None => self.args.url.clone(),
+                }),
             ]),
             Line::from(vec![
                 Span::styled("Method: ", Style::default().fg(Color::Yellow)),
@@ -457,15 +506,61 @@ impl Dashboard {
                 Span::styled("Concurrency: ", Style::default().fg(Color::Yellow)),
                 Span::raw(self.args.concurrency.to_string()),
             ]),
-            Line::from(vec![
-                Span::styled("Total Requests: ", Style::default().fg(Color::Yellow)),
-                Span::raw(self.args.number.to_string()),
-            ]),
+            match self.run_duration {
+                Some(duration) => Line::from(vec![
+                    Span::styled("Duration: ", Style::default().fg(Color::Yellow)),
+                    Span::raw(format_duration(duration)),
+                ]),
+                None => Line::from(vec![
+                    Span::styled("Total Requests: ", Style::default().fg(Color::Yellow)),
+                    Span::raw(self.args.number.to_string()),
+                ]),
+            },
+            match &self.stages {
+                Some(stages) => Line::from(vec![
+                    Span::styled("Stages: ", Style::default().fg(Color::Yellow)),
+                    Span::raw(format!(
+                        "{} up to {}",
+                        stages.stages.len(),
+                        stages.format_target(stages.max_target())
+                    )),
+                ]),
+                None => Line::from(vec![
+                    Span::styled("Rate: ", Style::default().fg(Color::Yellow)),
+                    Span::raw(match self.args.rate {
+                        Some(rate) => format!("{} req/s", rate),
+                        None => "unlimited".to_string(),
+                    }),
+                ]),
+            },
             Line::from(vec![
                 Span::styled("Timeout: ", Style::default().fg(Color::Yellow)),
                 Span::raw(self.args.timeout.to_string()),
             ]),
         ];
+        // Secrets are never displayed
+        if let Some(auth) = &self.auth {
+            params.push(Line::from(vec![
+                Span::styled("Auth: ", Style::default().fg(Color::Yellow)),
+                Span::raw(auth.clone()),
+            ]));
+        }
+        if let Some(feed) = &self.args.data_feed {
+            params.push(Line::from(vec![
+                Span::styled("Data: ", Style::default().fg(Color::Yellow)),
+                Span::raw(feed.describe()),
+            ]));
+        }
+        if let Some(oauth2) = &self.args.oauth2 {
+            let token = oauth2.stats();
+            params.push(Line::from(vec![
+                Span::styled("Token: ", Style::default().fg(Color::Yellow)),
+                Span::raw(format!(
+                    "{} fetched, {} failed, avg {}ms",
+                    token.fetches, token.failures, token.avg_latency_ms
+                )),
+            ]));
+        }
 
         f.render_widget(
             Paragraph::new(params).block(
@@ -485,7 +580,7 @@ impl Dashboard {
             .constraints([Constraint::Percentage(20), Constraint::Percentage(80)])
             .split(area);
 
-        let percent = (self.stats.count * 100) / (self.args.number as usize).max(1);
+        let percent = self.progress();
 
         let progress_color = match percent {
             0..=25 => Color::Red,
@@ -495,24 +590,49 @@ impl Dashboard {
             _ => Color::Green,
         };
 
-        let progress_blocks = ["░", "▒", "▓", "█"];
-        let total_blocks = (chunks[1].width.saturating_sub(12)) as usize;
-        let filled_blocks = (percent as usize * total_blocks) / 100;
-
-        let progress_bar: String = progress_blocks[3].repeat(filled_blocks)
-            + &progress_blocks[0].repeat(total_blocks - filled_blocks);
-
-        if self.stats.count == (self.args.number as usize) && self.final_duration.is_none() {
+        if self.is_finished() && self.final_duration.is_none() {
             self.final_duration = Some(std::time::Instant::now() - self.elapsed);
         }
 
+        let time_progress = match self.run_duration {
+            Some(duration) => format!(
+                "{} / {}",
+                format_duration(self.elapsed.elapsed().min(duration)),
+                format_duration(duration)
+            ),
+            None => String::new(),
+        };
+
         let animated_progress = format!(
             "{} {}% {}",
             if percent == 100 { "" } else { "➡️" },
             percent,
-            { "" }
+            time_progress
         );
 
+        let progress_blocks = ["░", "▒", "▓", "█"];
+        let label_width = animated_progress.chars().count() as u16 + 4;
+        let total_blocks = (chunks[1].width.saturating_sub(label_width)) as usize;
+        let filled_blocks = (percent * total_blocks) / 100;
+
+        let progress_bar: String = progress_blocks[3].repeat(filled_blocks)
+            + &progress_blocks[0].repeat(total_blocks - filled_blocks);
+
+        let progress_title = match &self.stages {
+            Some(stages) if !self.is_finished() => {
+                let elapsed = self.elapsed.elapsed();
+                let index = stages.index_at(elapsed);
+                format!(
+                    "🚀 Progress · Stage {}/{}: {} · now {}",
+                    index + 1,
+                    stages.stages.len(),
+                    stages.format_stage(index),
+                    stages.format_target(stages.target_at(elapsed))
+                )
+            }
+            _ => "🚀 Progress".to_string(),
+        };
+
         let progress_line = Line::from(vec![
             Span::styled(
                 format!("{} ", animated_progress),
@@ -545,7 +665,7 @@ impl Dashboard {
         f.render_widget(
             Paragraph::new(vec![Line::from(vec![Span::styled(
                 formatted_duration,
-                Style::default().fg(if self.stats.count == self.args.number as usize {
+                Style::default().fg(if self.is_finished() {
                     Color::Green
                 } else {
                     Color::White
@@ -556,25 +676,70 @@ impl Dashboard {
         );
         f.render_widget(
             Paragraph::new(progress_line)
-                .block(Block::default().borders(Borders::ALL).title("🚀 Progress")),
+                .block(Block::default().borders(Borders::ALL).title(progress_title)),
             chunks[1],
         );
     }
 
     fn render_stats(&self, f: &mut Frame, area: Rect) {
-        let stats = vec![
-            ("Total", self.stats.count.to_string(), Color::Yellow),
+        let remaining = match self.run_duration {
+            Some(duration) => format_duration(duration.saturating_sub(self.elapsed.elapsed())),
+            None => (self.args.number as usize)
+                .saturating_sub(self.results.completed())
+                .to_string(),
+        };
+
+        let mut stats = vec![
+            ("Total", self.results.stats.count.to_string(), Color::Yellow),
+            ("Remaining", remaining, Color::LightYellow),
+            ("Sent", self.results.stats.sent.to_string(), Color::Cyan),
+            (
+                "Success",
+                self.results.stats.success.to_string(),
+                Color::Green,
+            ),
+            (
+                "Failed",
+                self.results.stats.failed.to_string(),
+                Color::LightRed,
+            ),
             (
-                "Remaining",
-                ((self.args.number as usize) - self.stats.count).to_string(),
-                Color::LightYellow,
+                "Timeouts",
+                self.results.stats.timeouts.to_string(),
+                Color::Red,
             ),
-            ("Sent", self.stats.sent.to_string(), Color::Cyan),
-            ("Success", self.stats.success.to_string(), Color::Green),
-            ("Failed", self.stats.failed.to_string(), Color::LightRed),
-            ("Timeouts", self.stats.timeouts.to_string(), Color::Red),
         ];
 
+        if self.results.scenario {
+            let iterations = &self.results.stats.iterations;
+            stats.push((
+                "Iterations",
+                format!(
+                    "{} ({} failed) P99: {}ms",
+                    iterations.len(),
+                    self.results.stats.failed_iterations,
+                    iterations.percentile(99.0)
+                ),
+                Color::Cyan,
+            ));
+        }
+
+        let target_rate = match &self.stages {
+            Some(stages) if stages.unit == StageUnit::Rate => {
+                Some(stages.target_at(self.elapsed.elapsed()))
+            }
+            _ => self.args.rate,
+        };
+
+        if let Some(rate) = target_rate {
+            let color = if self.results.stats.late_sends > 0 {
+                Color::Red
+            } else {
+                Color::Green
+            };
+            stats.push(("Rate (actual/target)", self.send_rate(rate), color));
+        }
+
         let base_percentage = 100 / stats.len() as u16;
         let remainder = 100 % stats.len() as u16;
         let constraints: Vec<Constraint> = stats
@@ -665,12 +830,16 @@ impl Dashboard {
             .split(area);
 
         // Render latency distribution
-        let latencies: Vec<u64> = self.histogram.iter().map(|(_, latency)| *latency).collect();
-        let latency_chart = self.render_latency_distribution(&latencies, chunks[2].width);
-        f.render_widget(latency_chart, chunks[2]);
+        let latency_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(4)])
+            .split(chunks[2]);
 
-        // Render stats
-        self.calculate_stats(&latencies);
+        // A histogram has a bounded size, its copy does not grow with the requests
+        let latencies = self.view().latencies.clone();
+        self.render_tail_latency(f, latency_chunks[1], &latencies);
+        let latency_chart = self.render_latency_distribution(&latencies, latency_chunks[0].width);
+        f.render_widget(latency_chart, latency_chunks[0]);
 
         let statistics_chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -714,55 +883,42 @@ impl Dashboard {
             f,
             min_max_avg_chunks[0],
             "Min",
-            format!("{:.2}ms", self.stats.min as f64),
+            format!("{:.2}ms", self.view().min() as f64),
             Color::Green,
         );
         self.render_stat_widget(
             f,
             min_max_avg_chunks[1],
             "Max",
-            format!("{:.2}ms", self.stats.max as f64),
+            format!("{:.2}ms", self.view().max() as f64),
             Color::Red,
         );
         self.render_stat_widget(
             f,
             min_max_avg_chunks[2],
             "Avg",
-            format!("{:.2}ms", self.stats.avg as f64),
+            format!("{:.2}ms", self.view().avg() as f64),
             Color::Yellow,
         );
         self.render_stat_widget(
             f,
             stats_chunks[0],
             "Std Dev",
-            format!("{:.2}ms", self.stats.std_dev as f64),
+            format!("{:.2}ms", self.view().std_dev() as f64),
             Color::Cyan,
         );
         self.render_stat_widget(
             f,
             stats_chunks[1],
             "Requests/Sec",
-            self.stats.rps.to_string(),
+            format!("{:.0}", self.view().rps()),
             Color::Magenta,
         );
         self.render_stat_widget(
             f,
             stats_chunks[2],
             "Cache Hit Rate",
-            if self.stats.count > 0 {
-                format!(
-                    "{:.2}%",
-                    (*self
-                        .stats
-                        .cache_categories
-                        .get(&CacheCategory::Hit)
-                        .unwrap_or(&0) as f64
-                        / self.stats.count as f64)
-                        * 100.0
-                )
-            } else {
-                "0%".to_string()
-            },
+            format!("{:.2}%", self.view().cache_hit_rate()),
             Color::Green,
         );
 
@@ -770,14 +926,14 @@ impl Dashboard {
             f,
             dns_chunks[0],
             "Avg DNS Lookup",
-            format!("{:.2}ms", self.stats.avg_dns_lookup as f64),
+            format!("{:.2}ms", self.view().avg_dns_lookup() as f64),
             Color::LightMagenta,
         );
         self.render_stat_widget(
             f,
             dns_chunks[1],
             "Avg DNS Resolution",
-            format!("{:.2}ms", self.stats.avg_dns_resolution as f64),
+            format!("{:.2}ms", self.view().avg_dns_resolution() as f64),
             Color::LightMagenta,
         );
         self.render_stat_widget(
@@ -786,8 +942,8 @@ impl Dashboard {
             "Total data",
             format!(
                 "{:.2}kb | {:.2}mb",
-                self.data_transfer / 1024.0,
-                self.data_transfer / 1024.0 / 1024.0
+                self.view().data_transfer as f64 / 1024.0,
+                self.view().data_transfer as f64 / 1024.0 / 1024.0
             ),
             Color::LightYellow,
         );
@@ -796,13 +952,63 @@ impl Dashboard {
             f,
             data_chunks[1],
             "Data Transfer",
-            format!("{:.2}kb/s", (self.stats.data as f64) / 1024.0),
+            format!("{:.2}kb/s", self.view().throughput() / 1024.0),
             Color::Yellow,
         );
 
-        // Render status codes distribution
-        let status_chart = self.render_status_codes(chunks[1].width);
-        f.render_widget(status_chart, chunks[1]);
+        // Render status codes distribution, with the schema violations below it
+        let schemas = self.args.labeled_schemas();
+        let status_area = if schemas.is_empty() {
+            chunks[1]
+        } else {
+            let status_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .split(chunks[1]);
+            self.render_schemas(f, status_chunks[1], &schemas);
+            status_chunks[0]
+        };
+        let status_chart = self.render_status_codes(status_area.width);
+        f.render_widget(status_chart, status_area);
+    }
+
+    fn render_schemas(&self, f: &mut Frame, area: Rect, schemas: &[(String, Arc<Schema>)]) {
+        let mut lines = Vec::new();
+        for (label, schema) in schemas {
+            let stats = schema.stats();
+            let color = if stats.violations > 0 {
+                Color::Red
+            } else {
+                Color::Green
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", label), Style::default().fg(Color::Cyan)),
+                Span::styled(
+                    format!(
+                        "{}/{} invalid ({:.1}%)",
+                        stats.violations,
+                        stats.validated,
+                        stats.violation_rate()
+                    ),
+                    Style::default().fg(color),
+                ),
+            ]));
+            for sample in &stats.samples {
+                lines.push(Line::from(Span::styled(
+                    format!("  {}: {}", sample.path, sample.message),
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+        }
+
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .title("Schema Violations")
+                    .borders(Borders::ALL),
+            ),
+            area,
+        );
     }
 
     fn render_stat_widget(
@@ -825,6 +1031,7 @@ impl Dashboard {
 
     fn render_status_codes(&self, area_width: u16) -> BarChart {
         let mut data: Vec<(String, u64)> = self
+            .view()
             .status_codes
             .iter()
             .map(|(code, count)| (format!("{:?}", code), *count as u64))
@@ -839,7 +1046,7 @@ impl Dashboard {
         ];
 
         for status in list_of_default_status {
-            if !self.status_codes.contains_key(&status) {
+            if !self.view().status_codes.contains_key(&status) {
                 data.push((format!("{:?}", status), 0));
             }
         }
@@ -858,9 +1065,13 @@ impl Dashboard {
                     data.iter()
                         .map(|(label, value)| {
                             let status_code: u16 = label.parse().unwrap_or(0);
+                            let expected = StatusCode::from_u16(status_code)
+                                .is_ok_and(|status| self.expect_status.contains(status));
+                            // Expected codes are green, whatever their class
                             let color = match status_code {
+                                _ if expected => Color::LightGreen,
                                 100..=199 => Color::Blue,
-                                200..=299 => Color::LightGreen,
+                                200..=299 => Color::Red,
                                 300..=399 => Color::Magenta,
                                 400..=499 => Color::Yellow,
                                 500..=599 => Color::Red,
@@ -881,19 +1092,267 @@ impl Dashboard {
             .label_style(Style::default().fg(Color::White))
             .block(
                 Block::default()
-                    .title("Status Codes Distribution")
+                    .title(self.view_title("Status Codes Distribution"))
                     .borders(Borders::ALL),
             )
     }
 
+    fn render_stages(&self, f: &mut Frame, area: Rect, stages: &Stages) {
+        let elapsed = self.final_duration.unwrap_or(self.elapsed.elapsed());
+        let current = stages.index_at(elapsed);
+
+        let rows: Vec<Row> = self
+            .results
+            .stages
+            .iter()
+            .enumerate()
+            .map(|(i, stats)| {
+                let latencies = &stats.latencies;
+
+                let style = if i == current && !self.is_finished() {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+
+                Row::new(vec![
+                    Cell::from(format!("{}", i + 1)),
+                    Cell::from(stages.format_stage(i)),
+                    Cell::from(stats.count.to_string()),
+                    Cell::from(stats.success.to_string()),
+                    Cell::from((stats.failed + stats.timeouts).to_string()),
+                    Cell::from(format!("{}ms", stats.avg())),
+                    Cell::from(format!("{}ms", latencies.percentile(99.0))),
+                    Cell::from(format!("{:.1}", stats.rps())),
+                ])
+                .style(style)
+            })
+            .collect();
+
+        let widths = [
+            Constraint::Length(2),
+            Constraint::Min(16),
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(7),
+        ];
+
+        f.render_widget(
+            Table::new(rows, widths)
+                .header(
+                    Row::new(vec!["#", "Stage", "Reqs", "OK", "Err", "Avg", "P99", "RPS"])
+                        .style(Style::default().fg(Color::Cyan)),
+                )
+                .block(Block::default().title("Stages").borders(Borders::ALL)),
+            area,
+        );
+    }
+
+    fn render_endpoints(&self, f: &mut Frame, area: Rect) {
+        let mut endpoints: Vec<(usize, &Stats)> =
+            self.results.endpoints.iter().enumerate().collect();
+        // Keys are computed once per endpoint, a percentile walks the whole histogram
+        if self.sort != EndpointSort::File {
+            endpoints.sort_by_cached_key(|(_, stats)| std::cmp::Reverse(self.sort.key(stats)));
+        }
+
+        let rows: Vec<Row> = endpoints
+            .into_iter()
+            .map(|(i, stats)| {
+                let latencies = &stats.latencies;
+
+                let style = if self.filter == Some(i) {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+
+                Row::new(vec![
+                    Cell::from(self.endpoints[i].clone()),
+                    Cell::from(stats.count.to_string()),
+                    Cell::from((stats.failed + stats.timeouts).to_string()),
+                    Cell::from(format!("{}ms", stats.avg())),
+                    Cell::from(format!("{}ms", latencies.percentile(99.0))),
+                    Cell::from(format!("{:.1}kb", stats.data_transfer as f64 / 1024.0)),
+                ])
+                .style(style)
+            })
+            .collect();
+
+        let widths = [
+            Constraint::Min(16),
+            Constraint::Length(7),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(10),
+        ];
+
+        // The sorted column is marked in the header
+        let header: Vec<String> = [
+            (EndpointSort::File, "Endpoint"),
+            (EndpointSort::Requests, "Reqs"),
+            (EndpointSort::Errors, "Err"),
+            (EndpointSort::Avg, "Avg"),
+            (EndpointSort::P99, "P99"),
+            (EndpointSort::Data, "Data"),
+        ]
+        .iter()
+        .map(|(sort, title)| {
+            if *sort == self.sort && *sort != EndpointSort::File {
+                format!("{}▼", title)
+            } else {
+                title.to_string()
+            }
+        })
+        .collect();
+
+        f.render_widget(
+            Table::new(rows, widths)
+                .header(Row::new(header).style(Style::default().fg(Color::Cyan)))
+                .block(
+                    Block::default()
+                        .title(if self.results.scenario {
+                            "Steps"
+                        } else {
+                            "Endpoints"
+                        })
+                        .borders(Borders::ALL),
+                ),
+            area,
+        );
+    }
+
+    fn render_checks(&self, f: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self
+            .checks
+            .iter()
+            .enumerate()
+            .map(|(i, expression)| {
+                let count = self
+                    .results
+                    .stats
+                    .checks
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_default();
+                let style = if count.failed > 0 {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default().fg(Color::Green)
+                };
+
+                Row::new(vec![
+                    Cell::from(expression.clone()),
+                    Cell::from(count.passed.to_string()),
+                    Cell::from(count.failed.to_string()),
+                    Cell::from(format!("{:.1}%", count.pass_rate())),
+                ])
+                .style(style)
+            })
+            .collect();
+
+        let widths = [
+            Constraint::Min(16),
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Length(7),
+        ];
+
+        f.render_widget(
+            Table::new(rows, widths)
+                .header(
+                    Row::new(vec!["Check", "Pass", "Fail", "Pass%"])
+                        .style(Style::default().fg(Color::Cyan)),
+                )
+                .block(Block::default().title("Checks").borders(Borders::ALL)),
+            area,
+        );
+    }
+
+    fn render_errors(&self, f: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        for (kind, count) in self.view().sorted_errors() {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("{} ", kind.label()),
+                    Style::default().fg(Color::Red),
+                ),
+                Span::styled(count.count.to_string(), Style::default().fg(Color::Yellow)),
+            ]));
+            for sample in &count.samples {
+                lines.push(Line::from(Span::styled(
+                    format!("  {}", sample),
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+        }
+
+        f.render_widget(
+            Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+                Block::default()
+                    .title(self.view_title("Errors"))
+                    .borders(Borders::ALL),
+            ),
+            area,
+        );
+    }
+
     fn render_request_log(&self, f: &mut Frame, area: Rect) {
+        // The errors panel shows up with the first error
+        let has_errors = !self.results.stats.errors.is_empty();
+        let panels = self.stages.is_some() as usize
+            + !self.endpoints.is_empty() as usize
+            + !self.checks.is_empty() as usize
+            + has_errors as usize;
+        let constraints = match panels {
+            0 => vec![Constraint::Percentage(50), Constraint::Percentage(50)],
+            1 => vec![
+                Constraint::Percentage(35),
+                Constraint::Percentage(30),
+                Constraint::Percentage(35),
+            ],
+            // The request log and the partial responses keep half of the width
+            n => [Constraint::Percentage(30), Constraint::Percentage(20)]
+                .into_iter()
+                .chain(std::iter::repeat_n(
+                    Constraint::Percentage(50 / n as u16),
+                    n,
+                ))
+                .collect(),
+        };
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .constraints(constraints)
             .split(area);
 
-        let items: Vec<ListItem> = self
+        let mut panel = 2;
+        if let Some(stages) = &self.stages {
+            self.render_stages(f, chunks[panel], stages);
+            panel += 1;
+        }
+        if !self.endpoints.is_empty() {
+            self.render_endpoints(f, chunks[panel]);
+            panel += 1;
+        }
+        if !self.checks.is_empty() {
+            self.render_checks(f, chunks[panel]);
+            panel += 1;
+        }
+        if has_errors {
+            self.render_errors(f, chunks[panel]);
+        }
+
+        let requests: Vec<&ResponseStats> = self
             .requests
+            .iter()
+            .filter(|req| self.filter.is_none() || req.endpoint == self.filter)
+            .collect();
+
+        let items: Vec<ListItem> = requests
             .iter()
             .map(|req| self.format_request_item(req))
             .collect();
@@ -902,32 +1361,25 @@ impl Dashboard {
             List::new(items)
                 .block(
                     Block::default()
-                        .title("Recent Requests")
+                        .title(self.view_title("Recent Requests"))
                         .borders(Borders::ALL),
                 )
                 .highlight_style(Style::default().add_modifier(Modifier::BOLD)),
             chunks[0],
         );
 
-        let partial_response_items: Vec<ListItem> = self
-            .requests
+        let partial_response_items: Vec<ListItem> = requests
             .iter()
             .filter(|req| req.partial_response.is_some())
             .map(|req| {
-                let status_text = if req.status_code.is_none() {
-                    "[TIMEOUT]".to_string()
+                let status_text = status_text(req);
+
+                let status_style = if req.is_success() {
+                    Style::default().fg(Color::Green)
                 } else {
-                    let status_code = req.status_code.unwrap();
-                    format!("[{}]", status_code)
+                    Style::default().fg(Color::Red)
                 };
 
-                let status_style =
-                    if req.status_code.is_none() || !req.status_code.unwrap().is_success() {
-                        Style::default().fg(Color::Red)
-                    } else {
-                        Style::default().fg(Color::Green)
-                    };
-
                 ListItem::new(Line::from(vec![
                     Span::styled(status_text, status_style),
                     Span::raw(" "),
@@ -971,3 +1423,12 @@ impl Dashboard {
         self.render_request_log(f, chunks[4]);
     }
 }
+
+/// Status of a request in the request log, e.g. [200 OK] or [CONNECTION REFUSED]
+fn status_text(stat: &ResponseStats) -> String {
+    match (stat.status_code, &stat.error) {
+        (Some(status_code), _) => format!("[{}]", status_code),
+        (None, Some(error)) => format!("[{}]", error.kind.label().to_uppercase()),
+        (None, None) => "[ERROR]".to_string(),
+    }
+}
@@ -61,3 +61,121 @@ pub async fn resolve_dns(
 
     Ok((dns_lookup_time, dns_resolution_time))
 }
+
+// Longest duration accepted, longer ones overflow the clock of the test
+const MAX_DURATION: std::time::Duration = std::time::Duration::from_secs(100 * 365 * 24 * 3600);
+
+/// Parse a human readable duration string into a Duration
+/// Supported units are ms, s, m and h, and they can be combined
+/// e.g. 500ms, 10s, 3m, 2h, 1m30s
+/// A number without a unit is interpreted as seconds
+/// # Arguments
+/// * `input` - A string slice that holds the duration
+/// # Returns
+/// A Result containing the parsed duration or an error message
+pub fn parse_duration(input: &str) -> Result<std::time::Duration, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Duration cannot be empty".to_string());
+    }
+
+    let out_of_range = || format!("Duration out of range: {}", input);
+    if let Ok(secs) = input.parse::<u64>() {
+        let duration = std::time::Duration::from_secs(secs);
+        if duration > MAX_DURATION {
+            return Err(out_of_range());
+        }
+        return Ok(duration);
+    }
+
+    let mut total = std::time::Duration::ZERO;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(|| format!("Missing unit in duration: {}", input))?;
+        if digits == 0 {
+            return Err(format!("Invalid duration: {}", input));
+        }
+        let value: f64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("Invalid duration: {}", input))?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_len] {
+            "ms" => value / 1000.0,
+            "s" => value,
+            "m" => value * 60.0,
+            "h" => value * 3600.0,
+            unit => return Err(format!("Invalid duration unit '{}' in {}", unit, input)),
+        };
+        total = std::time::Duration::try_from_secs_f64(seconds)
+            .ok()
+            .and_then(|duration| total.checked_add(duration))
+            .filter(|total| *total <= MAX_DURATION)
+            .ok_or_else(out_of_range)?;
+        rest = &rest[unit_len..];
+    }
+
+    Ok(total)
+}
+
+/// Format a duration as a short human readable string
+/// e.g. 1h02m03s, 2m05s, 10s
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m{:02}s", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("10"), Ok(Duration::from_secs(10)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("3m"), Ok(Duration::from_secs(180)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn parse_duration_combined_units() {
+        assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration(" 1h02m03s "), Ok(Duration::from_secs(3723)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_input() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("1e30s").is_err());
+    }
+
+    #[test]
+    fn parse_duration_rejects_out_of_range_values() {
+        assert!(parse_duration("100000000000000000000000s").is_err());
+        assert!(parse_duration("99999999999999999999h").is_err());
+        assert!(parse_duration("18446744073709551615").is_err());
+        assert!(parse_duration("1000000h").is_err());
+    }
+
+    #[test]
+    fn format_duration_units() {
+        assert_eq!(format_duration(Duration::from_secs(10)), "10s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+    }
+}